@@ -2,7 +2,12 @@
 
 use core::f32;
 
-use super::{frustum::Frustum, plane::Plane, ray::Ray, sphere::Sphere};
+use super::{
+    frustum::Frustum,
+    plane::{Plane, Side},
+    ray::Ray,
+    sphere::Sphere,
+};
 
 #[derive(Debug, Copy, Clone)]
 #[allow(unused)]
@@ -16,6 +21,14 @@ pub struct AABB {
     pub max: nalgebra_glm::Vec3,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// One of the three coordinate axes. Can be used to index into a vector with `axis as usize`.
+pub enum Axis {
+    X = 0,
+    Y = 1,
+    Z = 2,
+}
+
 #[allow(unused)]
 impl AABB {
     pub fn new() -> Self {
@@ -44,6 +57,37 @@ impl AABB {
         )
     }
 
+    /// Finds the size of an AABB along each axis
+    pub fn extents(&self) -> nalgebra_glm::Vec3 {
+        self.max - self.min
+    }
+
+    /// Finds the axis along which the AABB is the longest
+    pub fn longest_axis(&self) -> Axis {
+        let d = self.extents();
+        if d.x >= d.y && d.x >= d.z {
+            Axis::X
+        } else if d.y >= d.z {
+            Axis::Y
+        } else {
+            Axis::Z
+        }
+    }
+
+    /// Splits an AABB in two by a plane perpendicular to an axis. The value is clamped to lie within the AABB. Returns
+    /// the half below the plane first, and the half above the plane second.
+    pub fn split_at(&self, axis: Axis, value: f32) -> (AABB, AABB) {
+        let i = axis as usize;
+        let value = value.clamp(self.min[i], self.max[i]);
+
+        let mut below = *self;
+        below.max[i] = value;
+        let mut above = *self;
+        above.min[i] = value;
+
+        (below, above)
+    }
+
     /// This function finds the _surface_ area of an AABB.
     pub fn area(&self) -> f32 {
         let d = self.max - self.min;
@@ -55,9 +99,7 @@ impl AABB {
         let mut i = 0;
         for plane in frustum.planes() {
             let vmax = self.get_furthest_corner(plane);
-            let value = plane.normal().dot(&vmax) + plane.dist();
-            // println!("{}", value);
-            if value < 0.0 {
+            if plane.classify_point(vmax) == Side::Back {
                 let bounding_sphere = self.bounding_sphere();
                 let in_sphere = bounding_sphere.within_frustum(frustum);
                 if !in_sphere && debug {
@@ -77,30 +119,10 @@ impl AABB {
         true
     }
 
-    /// Determines whether or not an AABB intersects with a sphere
+    /// Determines whether or not an AABB intersects with a sphere. A sphere just touching the AABB counts as intersecting.
     pub fn within_sphere(&self, sphere: &Sphere) -> bool {
-        let mut radius_squared = sphere.radius.powf(2.0);
-
-        if (sphere.center.x < self.min.x) {
-            radius_squared -= (sphere.center.x - self.min.x).powf(2.0);
-        }
-        if (sphere.center.x > self.max.x) {
-            radius_squared -= (sphere.center.x - self.max.x).powf(2.0);
-        }
-        if (sphere.center.y < self.min.y) {
-            radius_squared -= (sphere.center.y - self.min.y).powf(2.0);
-        }
-        if (sphere.center.y > self.max.y) {
-            radius_squared -= (sphere.center.y - self.max.y).powf(2.0);
-        }
-        if (sphere.center.z < self.min.z) {
-            radius_squared -= (sphere.center.z - self.min.z).powf(2.0);
-        }
-        if (sphere.center.z > self.max.z) {
-            radius_squared -= (sphere.center.z - self.max.z).powf(2.0);
-        }
-
-        radius_squared > 0.0
+        let closest = self.closest_point(sphere.center);
+        nalgebra_glm::distance2(&closest, &sphere.center) <= sphere.radius * sphere.radius
     }
 
     /// Determines if a ray intersects an AABB
@@ -128,6 +150,39 @@ impl AABB {
         tmax >= tmin && tmax >= 0.0
     }
 
+    /// Finds where a ray enters an AABB, as the distance along the ray and the normal of the face it enters through.
+    /// If the ray starts inside the AABB, the distance is zero.
+    pub fn ray_entry(&self, ray: &Ray) -> Option<(f32, nalgebra_glm::Vec3)> {
+        let mut tmin = f32::MIN;
+        let mut tmax = f32::MAX;
+        let mut normal = nalgebra_glm::Vec3::zeros();
+
+        for axis in 0..3 {
+            let inv = 1.0 / ray.dir[axis];
+            let t1 = (self.min[axis] - ray.origin[axis]) * inv;
+            let t2 = (self.max[axis] - ray.origin[axis]) * inv;
+
+            // Entering through the min face means the normal points along the negative axis
+            let (near, far, sign) = if t1 < t2 {
+                (t1, t2, -1.0)
+            } else {
+                (t2, t1, 1.0)
+            };
+            if near > tmin {
+                tmin = near;
+                normal = nalgebra_glm::Vec3::zeros();
+                normal[axis] = sign;
+            }
+            tmax = tmax.min(far);
+        }
+
+        if tmax >= tmin && tmax >= 0.0 {
+            Some((tmin.max(0.0), normal))
+        } else {
+            None
+        }
+    }
+
     /// Calculates the bounding sphere for an AABB
     pub fn bounding_sphere(&self) -> Sphere {
         let center = self.center();
@@ -155,6 +210,20 @@ impl AABB {
         }
     }
 
+    /// Grows an AABB by a margin on all sides. Useful for fat AABBs in the BVH, so that moving objects don't need to
+    /// be reinserted every tick.
+    pub fn expand(&self, margin: f32) -> Self {
+        self.expand_xyz(nalgebra_glm::vec3(margin, margin, margin))
+    }
+
+    /// Grows an AABB by a separate margin along each axis, on both sides
+    pub fn expand_xyz(&self, margin: nalgebra_glm::Vec3) -> Self {
+        Self {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+
     /// Expands an AABB to fit all points in some iterator of points. This is __slow__!
     pub fn expand_to_fit(&mut self, points: impl IntoIterator<Item = nalgebra_glm::Vec3>) {
         for corner in points.into_iter() {
@@ -201,6 +270,74 @@ impl AABB {
         true
     }
 
+    /// Finds the smallest translation that moves this AABB out of the other AABB. Returns zero if they don't overlap.
+    pub fn penetration(&self, other: &AABB) -> nalgebra_glm::Vec3 {
+        // How far this AABB would have to move along each axis, in the positive and negative directions
+        let push_pos = other.max - self.min;
+        let push_neg = self.max - other.min;
+
+        let mut retval = nalgebra_glm::Vec3::zeros();
+        let mut smallest = f32::MAX;
+        for axis in 0..3 {
+            if push_pos[axis] <= 0.0 || push_neg[axis] <= 0.0 {
+                // Separated along this axis
+                return nalgebra_glm::Vec3::zeros();
+            }
+            let push = if push_pos[axis] < push_neg[axis] {
+                push_pos[axis]
+            } else {
+                -push_neg[axis]
+            };
+            if push.abs() < smallest {
+                smallest = push.abs();
+                retval = nalgebra_glm::Vec3::zeros();
+                retval[axis] = push;
+            }
+        }
+        retval
+    }
+
+    /// Finds when this AABB, moving by `velocity`, first touches the other AABB. Returns the fraction of the movement
+    /// at which they touch, from 0 to 1, or `None` if they don't touch during the movement. AABBs that already overlap
+    /// touch at 0.
+    ///
+    /// Unlike `intersects`, this catches AABBs that move all the way through each other in one step.
+    pub fn sweep(&self, velocity: nalgebra_glm::Vec3, other: &AABB) -> Option<f32> {
+        let mut t_enter = f32::MIN;
+        let mut t_exit = f32::MAX;
+        for axis in 0..3 {
+            let v = velocity[axis];
+            if v == 0.0 {
+                // Not moving along this axis, so they must already overlap along it
+                if self.max[axis] <= other.min[axis] || self.min[axis] >= other.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            // The times at which the near faces meet, and the far faces pass each other
+            let (near, far) = if v > 0.0 {
+                (
+                    other.min[axis] - self.max[axis],
+                    other.max[axis] - self.min[axis],
+                )
+            } else {
+                (
+                    other.max[axis] - self.min[axis],
+                    other.min[axis] - self.max[axis],
+                )
+            };
+            t_enter = t_enter.max(near / v);
+            t_exit = t_exit.min(far / v);
+        }
+
+        if t_enter > t_exit || t_enter > 1.0 || t_exit < 0.0 {
+            None
+        } else {
+            Some(t_enter.max(0.0))
+        }
+    }
+
     /// Determines whether this AABB _fully_ contains the other AABB
     pub fn contains(&self, other: &AABB) -> bool {
         let mut result = true;
@@ -223,6 +360,16 @@ impl AABB {
             && self.max.z <= point.z
     }
 
+    /// Finds the point within the AABB that is closest to a given point
+    pub fn closest_point(&self, point: nalgebra_glm::Vec3) -> nalgebra_glm::Vec3 {
+        nalgebra_glm::clamp_vec(&point, &self.min, &self.max)
+    }
+
+    /// Finds the distance from the AABB to a point. Points inside the AABB have a distance of zero.
+    pub fn distance_to_point(&self, point: nalgebra_glm::Vec3) -> f32 {
+        nalgebra_glm::distance(&self.closest_point(point), &point)
+    }
+
     /// Produces the corners of an AABB. This is _SLOW_!
     pub fn corners(&self) -> [nalgebra_glm::Vec3; 8] {
         [
@@ -260,3 +407,120 @@ impl AABB {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cube from -1 to 1 on each axis
+    fn unit_cube() -> AABB {
+        AABB::from_min_max(
+            nalgebra_glm::vec3(-1.0, -1.0, -1.0),
+            nalgebra_glm::vec3(1.0, 1.0, 1.0),
+        )
+    }
+
+    #[test]
+    fn closest_point_to_an_inside_point_is_itself() {
+        let point = nalgebra_glm::vec3(0.2, 0.3, -0.4);
+        assert_eq!(unit_cube().closest_point(point), point);
+        assert_eq!(unit_cube().distance_to_point(point), 0.0);
+    }
+
+    #[test]
+    fn closest_point_on_a_face() {
+        let on_face = nalgebra_glm::vec3(1.0, 0.5, 0.0);
+        assert_eq!(unit_cube().closest_point(on_face), on_face);
+        assert_eq!(unit_cube().distance_to_point(on_face), 0.0);
+
+        let past_face = nalgebra_glm::vec3(3.0, 0.5, 0.0);
+        assert_eq!(unit_cube().closest_point(past_face), on_face);
+        assert_eq!(unit_cube().distance_to_point(past_face), 2.0);
+    }
+
+    #[test]
+    fn closest_point_past_a_corner_is_the_corner() {
+        let point = nalgebra_glm::vec3(2.0, -2.0, 2.0);
+        assert_eq!(
+            unit_cube().closest_point(point),
+            nalgebra_glm::vec3(1.0, -1.0, 1.0)
+        );
+        assert!((unit_cube().distance_to_point(point) - 3.0_f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sphere_fully_inside() {
+        let sphere = Sphere::new(nalgebra_glm::vec3(0.0, 0.0, 0.0), 0.5);
+        assert!(unit_cube().within_sphere(&sphere));
+    }
+
+    #[test]
+    fn sphere_straddling_a_face() {
+        let sphere = Sphere::new(nalgebra_glm::vec3(1.2, 0.0, 0.0), 0.5);
+        assert!(unit_cube().within_sphere(&sphere));
+    }
+
+    #[test]
+    fn sphere_touching_a_corner_intersects() {
+        // (2, 3, 6) is exactly 7 units long, so the sphere's surface passes exactly through the corner at (1, 1, 1)
+        let center = nalgebra_glm::vec3(3.0, 4.0, 7.0);
+        assert!(unit_cube().within_sphere(&Sphere::new(center, 7.0)));
+        assert!(!unit_cube().within_sphere(&Sphere::new(center, 6.99)));
+    }
+
+    #[test]
+    fn sphere_touching_a_face_intersects() {
+        let sphere = Sphere::new(nalgebra_glm::vec3(0.0, -3.0, 0.0), 2.0);
+        assert!(unit_cube().within_sphere(&sphere));
+        let sphere = Sphere::new(nalgebra_glm::vec3(0.0, -3.0, 0.0), 1.99);
+        assert!(!unit_cube().within_sphere(&sphere));
+    }
+
+    #[test]
+    fn sweep_catches_a_box_passing_through_a_thin_wall() {
+        let bullet = AABB::from_min_max(
+            nalgebra_glm::vec3(-1.1, -0.1, -0.1),
+            nalgebra_glm::vec3(-0.9, 0.1, 0.1),
+        );
+        let wall = AABB::from_min_max(
+            nalgebra_glm::vec3(2.0, -5.0, -5.0),
+            nalgebra_glm::vec3(2.05, 5.0, 5.0),
+        );
+        let velocity = nalgebra_glm::vec3(10.0, 0.0, 0.0);
+
+        // Checking only where the bullet ends up misses the wall entirely
+        assert!(!bullet.translate(velocity).intersects(&wall));
+        let t = bullet.sweep(velocity, &wall).unwrap();
+        assert!((t - 0.29).abs() < 1e-5, "hit at {}", t);
+    }
+
+    #[test]
+    fn sweep_misses_a_box_off_to_the_side() {
+        let moving = unit_cube();
+        let other = unit_cube().translate(nalgebra_glm::vec3(5.0, 3.0, 0.0));
+        assert_eq!(
+            moving.sweep(nalgebra_glm::vec3(10.0, 0.0, 0.0), &other),
+            None
+        );
+    }
+
+    #[test]
+    fn sweep_misses_a_box_out_of_reach() {
+        let moving = unit_cube();
+        let other = unit_cube().translate(nalgebra_glm::vec3(0.0, 0.0, 10.0));
+        assert_eq!(
+            moving.sweep(nalgebra_glm::vec3(0.0, 0.0, 5.0), &other),
+            None
+        );
+    }
+
+    #[test]
+    fn sweep_of_overlapping_boxes_hits_immediately() {
+        let moving = unit_cube();
+        let other = unit_cube().translate(nalgebra_glm::vec3(1.0, 0.0, 0.0));
+        assert_eq!(
+            moving.sweep(nalgebra_glm::vec3(-1.0, 0.0, 0.0), &other),
+            Some(0.0)
+        );
+    }
+}
@@ -1,8 +1,9 @@
 //! This module defines an Apricot App.
 //!
 //! Apps are made up of a stack of `Scene`s. The `Scene` at the top of the stack is the one that is updated and rendered
-//! to the screen. Input information such as the keyboard state and mouse are passed along to the active scene, along
-//! with output contexts such as the renderer, which allow the scene to output to the screen.
+//! to the screen. Overlay scenes, like pause menus, are rendered on top of the scenes beneath them, which stay visible
+//! but aren't updated. Input information such as the keyboard state and mouse are passed along to the active scene,
+//! along with output contexts such as the renderer, which allow the scene to output to the screen.
 //!
 //! Scenes can either push new scenes onto the stack, or pop themselves off. This allows for fairly intuitive GUI
 //! management.
@@ -11,32 +12,56 @@ use std::cell::RefCell;
 use std::time::Instant;
 
 use sdl2::event::{Event, WindowEvent};
-use sdl2::keyboard::Scancode;
-use sdl2::sys::{SDL_GetPerformanceCounter, SDL_GetPerformanceFrequency};
-use sdl2::video::SwapInterval;
+use sdl2::keyboard::{Keycode, Scancode};
+use sdl2::mouse::MouseUtil;
+use sdl2::video::{SwapInterval, Window};
 use sdl2::Sdl;
 
-use super::render_core::RenderContext;
+use super::{
+    audio::AudioContext,
+    render_core::RenderContext,
+    task_pool::{TaskHandle, TaskPool},
+};
+
+/// A callback for a task spawned with `App::spawn_task_then`, which returns whether its task is done
+type TaskCallback = Box<dyn FnMut(&App) -> bool>;
 
 /// Struct that contains all information about an app, that is passed down to an active `Scene`.
 pub struct App {
     // Screen stuff
     /// The current size of the window
     pub window_size: nalgebra_glm::I32Vec2,
+    window: RefCell<Window>,
     /// The OpenGL rendering context
     pub renderer: RenderContext,
+    /// The audio context, for playing sounds and music. `None` if the audio device couldn't be opened, in which case
+    /// the app runs without sound.
+    pub audio: Option<AudioContext>,
+    /// Worker threads for CPU-heavy work that shouldn't stall the main thread
+    pub tasks: TaskPool,
+    task_callbacks: RefCell<Vec<TaskCallback>>,
 
     // Main loop stuff
     /// Whether or not the app is running
     pub running: bool,
+    /// Whether the window has keyboard focus
+    pub focused: bool,
     /// How many seconds the app has been up
     pub seconds: f32,
     /// How many ticks have occured since the app started
     pub ticks: usize,
+    /// How many seconds pass between each tick. Scenes are updated once per tick.
+    pub dt: f32,
+    frame_time_ms: f32,
 
     // User input state
     /// Static map of key states, where the boolean at index `k` determines if the scancode `k` is currently pressed
     pub keys: [bool; 256],
+    /// The keys that were pressed this tick, in order, including repeats from keys being held down
+    pub keys_pressed: Vec<Scancode>,
+    /// The text typed this tick. This comes from the OS's text input, so it follows the keyboard layout, shift, and
+    /// dead keys, unlike the keys pressed.
+    pub text_input: String,
     /// The position of the mouse, relative to the top-left corner of the screen
     pub mouse_pos: nalgebra_glm::Vec2,
     /// The relative motion of the mouse
@@ -45,14 +70,63 @@ pub struct App {
     pub mouse_left_down: bool,
     /// Whether the right mouse button is down
     pub mouse_right_down: bool,
+    /// Whether the middle mouse button is down
+    pub mouse_middle_down: bool,
     prev_mouse_left_down: bool,
     prev_mouse_right_down: bool,
+    prev_mouse_middle_down: bool,
     /// Whether the left mouse button was clicked (ie it was down the previous tick, but is now up)
     pub mouse_left_clicked: bool,
     /// Whether the right mouse button was clicked (ie it was down the previous tick, but is now up)
     pub mouse_right_clicked: bool,
+    /// Whether the middle mouse button was clicked (ie it was down the previous tick, but is now up)
+    pub mouse_middle_clicked: bool,
+    /// Whether the left mouse button was clicked twice in quick succession
+    pub mouse_double_clicked: bool,
+    /// When the left mouse button was last clicked, in seconds since the app started
+    last_left_click: Option<f32>,
     /// The motion of the mouse wheel
     pub mouse_wheel: f32,
+    mouse_util: MouseUtil,
+}
+
+/// The default number of milliseconds between each tick, for about 60 ticks per second
+pub const DEFAULT_TICK_INTERVAL_MS: u32 = 16;
+
+/// The default number of samples per pixel used to anti-alias the window
+pub const DEFAULT_MSAA_SAMPLES: u8 = 4;
+
+/// Settings that have to be known before the window is created
+#[derive(Copy, Clone, Debug)]
+pub struct AppOptions {
+    /// The size of the window. If set, this is used instead of the size passed to `run_with_options`.
+    pub window_size: Option<nalgebra_glm::I32Vec2>,
+    /// Whether swapping the window's buffers waits for the display's vertical sync
+    pub vsync: bool,
+    /// How many milliseconds pass between each tick
+    pub tick_interval_ms: u32,
+    /// How many samples per pixel the window is anti-aliased with. 0 turns anti-aliasing off.
+    pub msaa_samples: u8,
+    /// How many worker threads the app's task pool starts
+    pub worker_threads: usize,
+    /// Whether scenes stop being updated while the window doesn't have focus. They're still rendered.
+    pub pause_when_unfocused: bool,
+    /// The volume of all sounds and music, in the range [0, 1]
+    pub volume: f32,
+}
+
+impl Default for AppOptions {
+    fn default() -> Self {
+        Self {
+            window_size: None,
+            vsync: true,
+            tick_interval_ms: DEFAULT_TICK_INTERVAL_MS,
+            msaa_samples: DEFAULT_MSAA_SAMPLES,
+            worker_threads: TaskPool::default_threads(),
+            pause_when_unfocused: true,
+            volume: 1.0,
+        }
+    }
 }
 
 /// Starts a new app, with the `init` scene as the first scene in the stack.
@@ -61,14 +135,49 @@ pub fn run(
     window_title: &'static str,
     init: &dyn Fn(&App) -> RefCell<Box<dyn Scene>>,
 ) -> Result<(), String> {
+    run_with_options(window_size, window_title, AppOptions::default(), init)
+}
+
+/// Starts a new app like `run`, but with a given number of milliseconds between each tick.
+pub fn run_with_tick_interval(
+    window_size: nalgebra_glm::I32Vec2,
+    window_title: &'static str,
+    tick_interval_ms: u32,
+    init: &dyn Fn(&App) -> RefCell<Box<dyn Scene>>,
+) -> Result<(), String> {
+    let options = AppOptions {
+        tick_interval_ms,
+        ..AppOptions::default()
+    };
+    run_with_options(window_size, window_title, options, init)
+}
+
+/// Starts a new app like `run`, but with the given options.
+pub fn run_with_options(
+    window_size: nalgebra_glm::I32Vec2,
+    window_title: &'static str,
+    options: AppOptions,
+    init: &dyn Fn(&App) -> RefCell<Box<dyn Scene>>,
+) -> Result<(), String> {
+    let tick_interval_ms = options.tick_interval_ms;
+    assert!(tick_interval_ms > 0);
+    let window_size = options.window_size.unwrap_or(window_size);
+
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
-    let _audio_subsystem = sdl_context.audio()?;
+    // Kept alive until the app is done with the mixer
+    let audio_subsystem = sdl_context.audio();
 
     let gl_attr = video_subsystem.gl_attr();
     gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
     gl_attr.set_context_version(3, 3);
     gl_attr.set_double_buffer(true);
+    // Lets `RenderContext::set_srgb` convert the window's colors to sRGB
+    gl_attr.set_framebuffer_srgb_compatible(true);
+    if options.msaa_samples > 0 {
+        gl_attr.set_multisample_buffers(1);
+        gl_attr.set_multisample_samples(options.msaa_samples);
+    }
 
     let window = video_subsystem
         .window(window_title, window_size.x as u32, window_size.y as u32)
@@ -84,98 +193,135 @@ pub fn run(
 
     window
         .subsystem()
-        .gl_set_swap_interval(SwapInterval::VSync)
+        .gl_set_swap_interval(if options.vsync {
+            SwapInterval::VSync
+        } else {
+            SwapInterval::Immediate
+        })
         .unwrap();
 
     unsafe {
         gl::Enable(gl::DEPTH_TEST);
         gl::DepthFunc(gl::LESS);
         gl::Enable(gl::CULL_FACE);
-        gl::Enable(gl::MULTISAMPLE);
+        if options.msaa_samples > 0 {
+            gl::Enable(gl::MULTISAMPLE);
+        }
         gl::Enable(gl::BLEND);
         gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-        // gl::Enable(gl::FRAMEBUFFER_SRGB);
     }
 
+    let audio = match audio_subsystem
+        .as_ref()
+        .map_err(String::clone)
+        .and_then(|_| AudioContext::new())
+    {
+        Ok(audio) => Some(audio),
+        Err(e) => {
+            println!("Couldn't open audio, running without sound: {}", e);
+            None
+        }
+    };
+
     let mut app = App {
         window_size,
+        window: RefCell::new(window),
         renderer: RenderContext::new(),
+        audio,
+        tasks: TaskPool::new(options.worker_threads),
+        task_callbacks: RefCell::new(vec![]),
         // sdl_context,
         running: true,
+        focused: true,
         keys: [false; 256],
+        keys_pressed: vec![],
+        text_input: String::new(),
         mouse_pos: nalgebra_glm::vec2(0.0, 0.0),
         mouse_vel: nalgebra_glm::vec2(0.0, 0.0),
         mouse_left_down: false,
         mouse_right_down: false,
+        mouse_middle_down: false,
         prev_mouse_left_down: false,
         prev_mouse_right_down: false,
+        prev_mouse_middle_down: false,
         mouse_left_clicked: false,
         mouse_right_clicked: false,
+        mouse_middle_clicked: false,
+        mouse_double_clicked: false,
+        last_left_click: None,
         mouse_wheel: 0.0,
+        mouse_util: sdl_context.mouse(),
         seconds: 0.0,
         ticks: 0,
+        dt: tick_interval_ms as f32 / 1000.0,
+        frame_time_ms: 0.0,
     };
+    if let Some(audio) = &app.audio {
+        audio.set_master_volume(options.volume);
+    }
 
     let initial_scene = init(&app);
     let mut scene_stack: Vec<RefCell<Box<dyn Scene>>> = vec![];
     scene_stack.push(initial_scene);
 
     let time = Instant::now();
-    let mut start = time.elapsed().as_millis();
     let mut current;
     let mut previous = 0;
     let mut lag = 0;
     let mut elapsed;
-    let mut frames = 0;
-    const DELTA_T: u128 = 16;
+    let mut last_frame = time.elapsed().as_secs_f32();
+    let delta_t = tick_interval_ms as u128;
+    // The most ticks that will be caught up on after a long stall. Any time beyond this is dropped, so that a slow frame
+    // doesn't snowball into even slower frames.
+    const MAX_CATCH_UP_TICKS: u128 = 5;
     while app.running {
         app.seconds = time.elapsed().as_secs_f32();
+        app.poll_tasks();
         current = time.elapsed().as_millis();
         elapsed = current - previous;
 
         previous = current;
-        lag += elapsed;
+        lag = (lag + elapsed).min(MAX_CATCH_UP_TICKS * delta_t);
 
         let scene_stale = false;
-        while lag >= DELTA_T {
+        while lag >= delta_t {
             app.reset_input();
             app.poll_input(&sdl_context);
-            // sdl_context.mouse().warp_mouse_in_window(
-            //     &window,
-            //     app.screen_width / 2,
-            //     app.screen_height / 2,
-            // );
-            // sdl_context.mouse().set_relative_mouse_mode(true);
-
-            if let Some(scene_ref) = scene_stack.last() {
+
+            let paused = options.pause_when_unfocused && !app.focused;
+            if let Some(scene_ref) = scene_stack.last().filter(|_| !paused) {
                 scene_ref.borrow_mut().update(&app);
                 app.ticks += 1;
             }
 
             if !scene_stale {
                 // if scene isn't stale, purge the scene
-                lag -= DELTA_T;
+                lag -= delta_t;
             } else {
                 break;
             }
         }
 
         if !scene_stale {
-            app.renderer.int_screen_resolution = app.window_size;
-            if let Some(scene_ref) = scene_stack.last() {
+            app.renderer.set_screen_resolution(app.window_size);
+            unsafe {
+                gl::Viewport(0, 0, app.window_size.x, app.window_size.y);
+            }
+            // Render from the topmost scene that covers the whole screen, up through the overlays above it
+            let first_visible = scene_stack
+                .iter()
+                .rposition(|scene_ref| !scene_ref.borrow().is_overlay())
+                .unwrap_or(0);
+            for scene_ref in &scene_stack[first_visible..] {
                 scene_ref.borrow_mut().render(&app);
-                frames += 1;
+                // Flush each scene's 2D batch, so that it's drawn beneath the scenes above it
+                app.renderer.flush_2d();
             }
-            window.gl_swap_window();
-        }
+            app.window.borrow().gl_swap_window();
 
-        let end = unsafe { SDL_GetPerformanceCounter() };
-        let freq = unsafe { SDL_GetPerformanceFrequency() };
-        let seconds = (end as f64 - (start as f64)) / (freq as f64);
-        if seconds > 5.0 {
-            println!("5 seconds;  fps: {}", frames / 5);
-            start = end as u128;
-            frames = 0;
+            let now = time.elapsed().as_secs_f32();
+            app.record_frame_time((now - last_frame) * 1000.0);
+            last_frame = now;
         }
     }
 
@@ -183,11 +329,140 @@ pub fn run(
 }
 
 impl App {
+    /// The most seconds that can pass between two clicks for them to count as a double click
+    const DOUBLE_CLICK_SECONDS: f32 = 0.3;
+
+    /// How much of the newest frame time is blended into the running average
+    const FRAME_TIME_SMOOTHING: f32 = 0.05;
+
+    /// The number of frames rendered per second, averaged over the last several frames
+    pub fn fps(&self) -> f32 {
+        if self.frame_time_ms > 0.0 {
+            1000.0 / self.frame_time_ms
+        } else {
+            0.0
+        }
+    }
+
+    /// How many milliseconds a frame takes, averaged over the last several frames
+    pub fn frame_time_ms(&self) -> f32 {
+        self.frame_time_ms
+    }
+
+    /// How many seconds of updates have been simulated, which advances by exactly `dt` each tick. Unlike `seconds`,
+    /// this keeps counting up during the extra ticks that catch up after a slow frame.
+    pub fn tick_seconds(&self) -> f32 {
+        self.ticks as f32 * self.dt
+    }
+
+    /// How many simulated seconds have passed since `t`, a previous value of `tick_seconds`
+    pub fn elapsed_since(&self, t: f32) -> f32 {
+        self.tick_seconds() - t
+    }
+
+    /// Runs `task` on a worker thread. The task must not use OpenGL; poll the returned handle from the main thread to
+    /// get its result, and upload anything it made to the GPU there.
+    pub fn spawn_task<T: Send + 'static>(
+        &self,
+        task: impl FnOnce() -> T + Send + 'static,
+    ) -> TaskHandle<T> {
+        self.tasks.spawn(task)
+    }
+
+    /// Runs `task` on a worker thread like `spawn_task`, then calls `then` with its result on the main thread, where
+    /// OpenGL can be used. `then` is called at the start of the first frame after the task finishes.
+    pub fn spawn_task_then<T: Send + 'static>(
+        &self,
+        task: impl FnOnce() -> T + Send + 'static,
+        then: impl FnOnce(T, &App) + 'static,
+    ) {
+        let mut handle = self.spawn_task(task);
+        let mut then = Some(then);
+        self.task_callbacks
+            .borrow_mut()
+            .push(Box::new(move |app: &App| match handle.poll() {
+                Some(result) => {
+                    (then.take().unwrap())(result, app);
+                    true
+                }
+                None => !handle.is_running(),
+            }));
+    }
+
+    /// Calls the callbacks of tasks spawned with `spawn_task_then` that have finished. Called once per frame.
+    pub fn poll_tasks(&self) {
+        // Taken out so that callbacks can spawn more tasks
+        let mut callbacks = self.task_callbacks.take();
+        callbacks.retain_mut(|callback| !callback(self));
+        self.task_callbacks.borrow_mut().append(&mut callbacks);
+    }
+
+    /// The title of the window
+    pub fn title(&self) -> String {
+        self.window.borrow().title().to_string()
+    }
+
+    /// Changes the title of the window, for showing things like the level name or that the game is paused
+    pub fn set_title(&self, title: &str) -> Result<(), String> {
+        self.window
+            .borrow_mut()
+            .set_title(title)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Hides and locks the cursor to the window, so that `mouse_vel` reports unbounded relative motion. Useful for
+    /// first-person cameras. While on, `mouse_pos` is not updated, and pressing Escape turns relative mode off.
+    pub fn set_relative_mouse(&self, on: bool) {
+        self.mouse_util.set_relative_mouse_mode(on);
+    }
+
+    /// Whether relative mouse mode is on
+    pub fn relative_mouse(&self) -> bool {
+        self.mouse_util.relative_mouse_mode()
+    }
+
+    /// Shows or hides the OS cursor while it's over the window, for games that draw their own cursor. This is separate
+    /// from relative mouse mode, which always hides the cursor while it's on.
+    pub fn set_cursor_visible(&self, visible: bool) {
+        self.mouse_util.show_cursor(visible);
+    }
+
+    /// Whether the OS cursor is shown while it's over the window
+    pub fn cursor_visible(&self) -> bool {
+        self.mouse_util.is_cursor_showing()
+    }
+
+    /// The printable character that the key at a scancode produces in the current keyboard layout, if any. Letters are
+    /// uppercase while shift is held, but other shifted characters and dead keys aren't, so use `text_input` for typing
+    /// text.
+    pub fn key_char(&self, sc: Scancode) -> Option<char> {
+        // SDL keycodes for printable keys are the characters they produce
+        let keycode = Keycode::from_scancode(sc)? as i32;
+        let c = char::from_u32(keycode as u32)
+            .filter(|c| !c.is_control() && (c.is_ascii() || c.is_alphabetic()))?;
+        if self.keys[Scancode::LShift as usize] || self.keys[Scancode::RShift as usize] {
+            c.to_uppercase().next()
+        } else {
+            Some(c)
+        }
+    }
+
+    fn record_frame_time(&mut self, frame_time_ms: f32) {
+        if self.frame_time_ms == 0.0 {
+            self.frame_time_ms = frame_time_ms;
+        } else {
+            self.frame_time_ms += (frame_time_ms - self.frame_time_ms) * Self::FRAME_TIME_SMOOTHING;
+        }
+    }
+
     fn reset_input(&mut self) {
         self.mouse_vel = nalgebra_glm::vec2(0.0, 0.0);
         self.mouse_wheel = 0.0;
+        self.keys_pressed.clear();
+        self.text_input.clear();
         self.prev_mouse_left_down = self.mouse_left_down;
         self.prev_mouse_right_down = self.mouse_right_down;
+        self.prev_mouse_middle_down = self.mouse_middle_down;
     }
 
     fn poll_input(&mut self, sdl_context: &Sdl) {
@@ -201,19 +476,24 @@ impl App {
                 Event::MouseMotion {
                     x, y, xrel, yrel, ..
                 } => {
-                    self.mouse_pos = nalgebra_glm::vec2(x as f32, y as f32);
-                    self.mouse_vel = nalgebra_glm::vec2(xrel as f32, yrel as f32);
+                    if !self.relative_mouse() {
+                        self.mouse_pos = nalgebra_glm::vec2(x as f32, y as f32);
+                    }
+                    // Accumulate, in case there are multiple motion events in one tick
+                    self.mouse_vel += nalgebra_glm::vec2(xrel as f32, yrel as f32);
                 }
 
                 Event::MouseButtonDown { mouse_btn, .. } => match mouse_btn {
                     sdl2::mouse::MouseButton::Left => self.mouse_left_down = true,
                     sdl2::mouse::MouseButton::Right => self.mouse_right_down = true,
+                    sdl2::mouse::MouseButton::Middle => self.mouse_middle_down = true,
                     _ => {}
                 },
 
                 Event::MouseButtonUp { mouse_btn, .. } => match mouse_btn {
                     sdl2::mouse::MouseButton::Left => self.mouse_left_down = false,
                     sdl2::mouse::MouseButton::Right => self.mouse_right_down = false,
+                    sdl2::mouse::MouseButton::Middle => self.mouse_middle_down = false,
                     _ => {}
                 },
 
@@ -221,17 +501,46 @@ impl App {
                     self.mouse_wheel = y as f32;
                 }
 
-                Event::Window { win_event, .. } => {
-                    if let WindowEvent::Resized(new_width, new_height) = win_event {
-                        self.window_size = nalgebra_glm::I32Vec2::new(new_width, new_height)
-                    }
+                // `SizeChanged` is sent for every resize, `Resized` only for ones made by the user
+                Event::Window {
+                    win_event:
+                        WindowEvent::Resized(new_width, new_height)
+                        | WindowEvent::SizeChanged(new_width, new_height),
+                    ..
+                } => {
+                    self.window_size = nalgebra_glm::I32Vec2::new(new_width, new_height);
+                    // Refit the 2D camera right away, so that scenes updated this tick lay out for the new size
+                    self.renderer.set_screen_resolution(self.window_size);
+                }
+
+                Event::Window {
+                    win_event: WindowEvent::FocusGained,
+                    ..
+                } => self.focused = true,
+
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
+                    ..
+                } => {
+                    self.focused = false;
+                    // Keys and buttons released while unfocused never send an up event, so let go of them now
+                    self.keys = [false; 256];
+                    self.mouse_left_down = false;
+                    self.mouse_right_down = false;
+                    self.mouse_middle_down = false;
                 }
 
                 Event::KeyDown { scancode, .. } => match scancode {
                     Some(sc) => {
                         self.keys[sc as usize] = true;
+                        self.keys_pressed.push(sc);
                         if self.keys[Scancode::Escape as usize] {
-                            self.running = false
+                            if self.relative_mouse() {
+                                // Release the mouse first, so that menus can be used
+                                self.set_relative_mouse(false);
+                            } else {
+                                self.running = false
+                            }
                         }
                     }
                     None => {}
@@ -242,12 +551,60 @@ impl App {
                     None => {}
                 },
 
+                Event::TextInput { text, .. } => {
+                    self.text_input.push_str(&text);
+                }
+
                 _ => {}
             }
         }
 
         self.mouse_left_clicked = !self.prev_mouse_left_down && self.mouse_left_down;
         self.mouse_right_clicked = !self.prev_mouse_right_down && self.mouse_right_down;
+        self.mouse_middle_clicked = !self.prev_mouse_middle_down && self.mouse_middle_down;
+
+        self.mouse_double_clicked = false;
+        if self.mouse_left_clicked {
+            match self.last_left_click {
+                Some(last) if self.seconds - last <= Self::DOUBLE_CLICK_SECONDS => {
+                    self.mouse_double_clicked = true;
+                    // Don't let a third click count as another double click
+                    self.last_left_click = None;
+                }
+                _ => self.last_left_click = Some(self.seconds),
+            }
+        }
+    }
+}
+
+/// Measures time since it was started, for cooldowns and animations. Timers count simulated time, so they advance the
+/// same amount every tick regardless of the frame rate.
+#[derive(Copy, Clone, Debug)]
+pub struct Timer {
+    start_tick: usize,
+}
+
+impl Timer {
+    /// Starts a timer at the current tick
+    pub fn start(app: &App) -> Self {
+        Self {
+            start_tick: app.ticks,
+        }
+    }
+
+    /// Restarts the timer at the current tick
+    pub fn restart(&mut self, app: &App) {
+        self.start_tick = app.ticks;
+    }
+
+    /// How many seconds have passed since the timer was started
+    pub fn elapsed(&self, app: &App) -> f32 {
+        app.ticks.saturating_sub(self.start_tick) as f32 * app.dt
+    }
+
+    /// Whether at least `duration` seconds have passed since the timer was started
+    pub fn finished(&self, app: &App, duration: f32) -> bool {
+        self.elapsed(app) >= duration
     }
 }
 
@@ -256,4 +613,10 @@ pub trait Scene {
     // TODO: Return a "command" enum so that scene's can affect App state
     fn update(&mut self, app: &App);
     fn render(&mut self, app: &App);
+
+    /// Whether the scene is drawn over the scenes beneath it, like a pause menu or a modal dialog. The scenes beneath
+    /// an overlay are still rendered, but only the top scene is updated.
+    fn is_overlay(&self) -> bool {
+        false
+    }
 }
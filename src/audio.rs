@@ -1,8 +1,18 @@
-//! WARNING: Audio not fully tested yet, I don't think it works
+//! This module deals with playing sounds and music.
+//!
+//! WARNING: `AudioManager` is not fully tested yet, I don't think it works. Prefer the `AudioContext` on the `App`.
 
-use std::sync::{Arc, Mutex};
+use std::{
+    cell::RefCell,
+    sync::{Arc, Mutex},
+};
 
-use sdl2::mixer::{self, Chunk};
+use sdl2::mixer::{self, Chunk, Music, Sdl2MixerContext};
+
+use super::{
+    camera::Camera,
+    render_core::{OpaqueId, ResourceManager},
+};
 
 enum SoundCommand {
     Play(String, i32),
@@ -105,3 +115,248 @@ impl Default for AudioResource {
         Self { audio_mgr: todo!() }
     }
 }
+
+/// Plays sound effects and music. Owned by the `App`, and lives on the main thread alongside the renderer.
+pub struct AudioContext {
+    _mixer_context: Sdl2MixerContext,
+    sound_manager: RefCell<ResourceManager<Chunk, SoundId>>,
+    music: RefCell<Option<Music<'static>>>,
+    music_volume: RefCell<f32>,
+    master_volume: RefCell<f32>,
+    hearing_distance: RefCell<f32>,
+    // How many sounds each channel has played, so that handles to a finished sound can't control the next one
+    channel_generations: RefCell<Vec<u64>>,
+}
+
+/// Opaque type used by the audio context to associate sounds.
+#[derive(Copy, Clone, Debug)]
+pub struct SoundId(usize);
+
+/// A handle to a playing spatial sound, used to move the sound's source around while it plays. Once the sound ends,
+/// the handle no longer does anything, even if another sound plays on the same channel.
+#[derive(Copy, Clone, Debug)]
+pub struct SoundHandle {
+    channel: mixer::Channel,
+    generation: u64,
+}
+
+impl AudioContext {
+    const NUM_CHANNELS: i32 = 16;
+
+    /// Opens the audio device. The SDL audio subsystem must already be initialized.
+    pub fn new() -> Result<Self, String> {
+        let _mixer_context = mixer::init(mixer::InitFlag::OGG)?;
+        mixer::open_audio(44_100, mixer::AUDIO_S16LSB, mixer::DEFAULT_CHANNELS, 1_024)?;
+        mixer::allocate_channels(Self::NUM_CHANNELS);
+        Ok(Self {
+            _mixer_context,
+            sound_manager: RefCell::new(ResourceManager::new()),
+            music: RefCell::new(None),
+            music_volume: RefCell::new(1.0),
+            master_volume: RefCell::new(1.0),
+            hearing_distance: RefCell::new(100.0),
+            channel_generations: RefCell::new(vec![0; Self::NUM_CHANNELS as usize]),
+        })
+    }
+
+    /// Loads a sound effect from a file, relative to the crate directory
+    pub fn load_sound(
+        &self,
+        file_path: &str,
+        name: Option<&'static str>,
+    ) -> Result<SoundId, String> {
+        let chunk = Chunk::from_file(file_path)?;
+        Ok(self.sound_manager.borrow_mut().add(chunk, name))
+    }
+
+    /// Get a sound ID from it's name
+    pub fn get_sound_id_from_name(&self, name: &'static str) -> Option<SoundId> {
+        self.sound_manager.borrow().get_id_from_name(name)
+    }
+
+    /// Plays a sound once on the first free channel.
+    /// - volume: [0, 1], anything outside is clamped.
+    pub fn play(&self, sound: SoundId, volume: f32) -> Result<(), String> {
+        self.start(sound, volume, (0, 0), 0)?;
+        Ok(())
+    }
+
+    /// Plays a sound once from a position in the world, attenuated and panned relative to the camera.
+    /// - volume: [0, 1], anything outside is clamped.
+    pub fn play_at(
+        &self,
+        sound: SoundId,
+        volume: f32,
+        world_pos: nalgebra_glm::Vec3,
+        camera: &Camera,
+    ) -> Result<SoundHandle, String> {
+        self.play_spatial(sound, volume, world_pos, camera, 0)
+    }
+
+    /// Loops a sound forever from a position in the world. Call `update_position` each frame to keep the sound in
+    /// place as the camera moves, and `stop` to end it.
+    pub fn loop_at(
+        &self,
+        sound: SoundId,
+        volume: f32,
+        world_pos: nalgebra_glm::Vec3,
+        camera: &Camera,
+    ) -> Result<SoundHandle, String> {
+        self.play_spatial(sound, volume, world_pos, camera, -1)
+    }
+
+    /// Moves the source of a playing spatial sound, relative to the camera. Does nothing if the sound has ended.
+    pub fn update_position(
+        &self,
+        handle: SoundHandle,
+        world_pos: nalgebra_glm::Vec3,
+        camera: &Camera,
+    ) -> Result<(), String> {
+        if !self.is_playing(handle) {
+            return Ok(());
+        }
+        let (angle, distance) = self.listener_relative(world_pos, camera);
+        handle.channel.set_position(angle, distance)
+    }
+
+    /// Stops a playing spatial sound. Does nothing if the sound has already ended.
+    pub fn stop(&self, handle: SoundHandle) {
+        if !self.is_playing(handle) {
+            return;
+        }
+        handle.channel.halt();
+        // Unregisters the panning effect, so that it doesn't linger on the channel
+        let _ = handle.channel.set_position(0, 0);
+    }
+
+    /// Whether the sound behind a handle is still playing
+    pub fn is_playing(&self, handle: SoundHandle) -> bool {
+        self.channel_generations.borrow()[handle.channel.0 as usize] == handle.generation
+            && handle.channel.is_playing()
+    }
+
+    /// The distance at which spatial sounds become inaudible
+    pub fn hearing_distance(&self) -> f32 {
+        *self.hearing_distance.borrow()
+    }
+
+    /// Sets the distance at which spatial sounds become inaudible. Sounds that are already playing are only affected
+    /// once their position is updated.
+    pub fn set_hearing_distance(&self, distance: f32) {
+        *self.hearing_distance.borrow_mut() = distance;
+    }
+
+    /// Streams a music track from a file, replacing any music that is already playing.
+    /// - loops: how many times to play the track, or -1 to loop forever.
+    pub fn play_music(&self, file_path: &str, loops: i32) -> Result<(), String> {
+        let music = Music::from_file(file_path)?;
+        music.play(loops)?;
+        *self.music.borrow_mut() = Some(music);
+        Ok(())
+    }
+
+    /// Sets the volume of the music. Volume is in the range [0, 1], anything outside is clamped.
+    pub fn set_music_volume(&self, volume: f32) {
+        *self.music_volume.borrow_mut() = volume;
+        Music::set_volume(self.mixer_volume(volume));
+    }
+
+    /// Sets the volume that all sounds and music are scaled by. Volume is in the range [0, 1], anything outside is
+    /// clamped. Sounds that are already playing keep their volume.
+    pub fn set_master_volume(&self, volume: f32) {
+        *self.master_volume.borrow_mut() = volume.clamp(0.0, 1.0);
+        let music_volume = *self.music_volume.borrow();
+        self.set_music_volume(music_volume);
+    }
+
+    /// The volume that all sounds and music are scaled by
+    pub fn master_volume(&self) -> f32 {
+        *self.master_volume.borrow()
+    }
+
+    /// Stops the music, if any is playing
+    pub fn stop_music(&self) {
+        Music::halt();
+        *self.music.borrow_mut() = None;
+    }
+
+    fn play_spatial(
+        &self,
+        sound: SoundId,
+        volume: f32,
+        world_pos: nalgebra_glm::Vec3,
+        camera: &Camera,
+        loops: i32,
+    ) -> Result<SoundHandle, String> {
+        let position = self.listener_relative(world_pos, camera);
+        self.start(sound, volume, position, loops)
+    }
+
+    /// Plays a sound on the first free channel, panned to an angle and mixer distance. A position of (0, 0) plays the
+    /// sound unpanned, and clears any panning left on the channel by an earlier spatial sound.
+    fn start(
+        &self,
+        sound: SoundId,
+        volume: f32,
+        (angle, distance): (i16, u8),
+        loops: i32,
+    ) -> Result<SoundHandle, String> {
+        let manager = self.sound_manager.borrow();
+        let chunk = manager.get_from_id(sound).ok_or("Invalid sound id")?;
+        let channel = (0..Self::NUM_CHANNELS)
+            .map(mixer::Channel)
+            .find(|channel| !channel.is_playing())
+            .ok_or("No free channel to play sound on")?;
+
+        channel.set_position(angle, distance)?;
+        channel.set_volume(self.mixer_volume(volume));
+        channel.play(chunk, loops)?;
+
+        let mut generations = self.channel_generations.borrow_mut();
+        generations[channel.0 as usize] += 1;
+        Ok(SoundHandle {
+            channel,
+            generation: generations[channel.0 as usize],
+        })
+    }
+
+    /// Finds the angle (in degrees clockwise from the camera's forward direction) and mixer distance of a point
+    fn listener_relative(&self, world_pos: nalgebra_glm::Vec3, camera: &Camera) -> (i16, u8) {
+        let to_source = world_pos - camera.position();
+        let forward = (camera.lookat() - camera.position()).normalize();
+        let right = nalgebra_glm::cross(&forward, &camera.up()).normalize();
+
+        let angle = to_source
+            .dot(&right)
+            .atan2(to_source.dot(&forward))
+            .to_degrees();
+        let angle = (angle.round() as i16).rem_euclid(360);
+
+        let distance = to_source.magnitude() / self.hearing_distance();
+        let distance = (distance.clamp(0.0, 1.0) * 255.0) as u8;
+
+        (angle, distance)
+    }
+
+    fn mixer_volume(&self, volume: f32) -> i32 {
+        (volume.clamp(0.0, 1.0) * self.master_volume() * mixer::MAX_VOLUME as f32) as i32
+    }
+}
+
+impl Drop for AudioContext {
+    fn drop(&mut self) {
+        self.stop_music();
+        mixer::Channel::all().halt();
+        mixer::close_audio();
+    }
+}
+
+impl OpaqueId for SoundId {
+    fn new(id: usize) -> Self {
+        SoundId(id)
+    }
+
+    fn as_usize(&self) -> usize {
+        self.0
+    }
+}
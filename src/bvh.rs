@@ -1,5 +1,7 @@
 //! This module implements a Bounding Volume Hierarchy. This data structure allows for efficient spatial lookup of
-//! objects that intersect an AABB, a frustum, a sphere, or are struck by a ray.
+//! objects that intersect an AABB, a frustum, a sphere, or are struck by a ray, and of the objects nearest to a point.
+
+use std::{cmp::Ordering, collections::BinaryHeap};
 
 use rand::{rngs::StdRng, Rng, SeedableRng};
 
@@ -10,6 +12,8 @@ pub struct BVH<Object: Copy + Clone> {
     nodes: Vec<BVHNode<Object>>,
     root_id: BVHNodeId,
     rng: StdRng,
+    /// How many objects are in the tree
+    len: usize,
 }
 
 /// Opaque ID for a BVH node
@@ -38,6 +42,13 @@ pub struct BVHFrustumIterator<'a, Object: Copy + Clone> {
     debug: bool,
 }
 
+/// Iterator returned when iterating over the items in a BVH that intersect with an AABB
+pub struct BVHAABBIterator<'a, Object: Copy + Clone> {
+    bvh: &'a BVH<Object>, // Reference to the tree
+    aabb: &'a AABB,
+    stack: Vec<BVHNodeId>,
+}
+
 /// Iterator returned when iterating over the items in a BVH that intersect with a sphere
 pub struct BVHSphereIterator<'a, Object: Copy + Clone> {
     bvh: &'a BVH<Object>, // Reference to the tree
@@ -52,6 +63,12 @@ pub struct BVHRayIterator<'a, Object: Copy + Clone> {
     stack: Vec<BVHNodeId>,
 }
 
+/// A node waiting to be visited by `k_nearest`, ordered so that the nearest node is at the top of a `BinaryHeap`
+struct NearestCandidate {
+    distance: f32,
+    node_id: BVHNodeId,
+}
+
 impl<Object: Copy + Clone> BVH<Object> {
     const AABB_EXTENSION: f32 = 0.1;
     const AABB_MULTIPLIER: f32 = 2.0;
@@ -62,9 +79,20 @@ impl<Object: Copy + Clone> BVH<Object> {
             nodes: vec![],
             root_id: INVALID_BVH_NODE_ID,
             rng: rand::rngs::StdRng::from_entropy(),
+            len: 0,
         }
     }
 
+    /// How many objects are in the BVH
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the BVH has no objects
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     /// Insert an object into a BVH, with a given AABB
     pub fn insert(&mut self, object: Object, aabb: AABB) -> BVHNodeId {
         let proxy_id = self.allocate_node(object, aabb);
@@ -81,6 +109,7 @@ impl<Object: Copy + Clone> BVH<Object> {
         self.set_volume(proxy_id, new_aabb);
 
         self.insert_leaf(proxy_id);
+        self.len += 1;
 
         proxy_id
     }
@@ -91,6 +120,7 @@ impl<Object: Copy + Clone> BVH<Object> {
         assert!(self.node_at(node_id).is_leaf());
 
         self.remove_leaf(node_id);
+        self.len -= 1;
         // TODO: Free the node id
     }
 
@@ -278,6 +308,21 @@ impl<Object: Copy + Clone> BVH<Object> {
         }
     }
 
+    /// Iterate through all objects in the BVH that intersect with a given AABB
+    pub fn iter_aabb<'a>(&'a self, aabb: &'a AABB) -> BVHAABBIterator<'a, Object> {
+        let mut stack = Vec::new();
+
+        if self.root_id != INVALID_BVH_NODE_ID {
+            stack.push(self.root_id);
+        }
+
+        BVHAABBIterator {
+            bvh: self,
+            aabb,
+            stack,
+        }
+    }
+
     /// Iterate through all objects in the BVH that intersect with a given sphere
     pub fn iter_sphere<'a>(&'a self, sphere: &'a Sphere) -> BVHSphereIterator<'a, Object> {
         let mut stack = Vec::new();
@@ -308,6 +353,70 @@ impl<Object: Copy + Clone> BVH<Object> {
         }
     }
 
+    /// Finds the `k` objects nearest to a point, sorted from nearest to farthest, along with their distance to the
+    /// point. Distances are measured to the object's volume in the BVH, which is slightly larger than the AABB it was
+    /// inserted with, and are 0 for objects whose volume contains the point.
+    pub fn k_nearest(&self, point: nalgebra_glm::Vec3, k: usize) -> Vec<(Object, f32)> {
+        let mut retval: Vec<(Object, f32)> = Vec::with_capacity(k);
+        if k == 0 || self.root_id == INVALID_BVH_NODE_ID {
+            return retval;
+        }
+
+        // Visit nodes nearest-first, so that once the nearest unvisited node is farther than the k-th best object,
+        // nothing left can beat it
+        let mut queue = BinaryHeap::new();
+        queue.push(NearestCandidate {
+            distance: self.node_at(self.root_id).volume.distance_to_point(point),
+            node_id: self.root_id,
+        });
+        while let Some(NearestCandidate { distance, node_id }) = queue.pop() {
+            if retval.len() == k && distance >= retval[k - 1].1 {
+                break;
+            }
+
+            let node = self.node_at(node_id);
+            if let Some(object) = node.object {
+                let index = retval.partition_point(|(_, other)| *other <= distance);
+                retval.insert(index, (object, distance));
+                retval.truncate(k);
+            }
+            for child_id in [node.left, node.right] {
+                if child_id != INVALID_BVH_NODE_ID {
+                    queue.push(NearestCandidate {
+                        distance: self.node_at(child_id).volume.distance_to_point(point),
+                        node_id: child_id,
+                    });
+                }
+            }
+        }
+        retval
+    }
+
+    /// Collects the volume of every node in the BVH down to a maximum depth, along with the node's depth and whether it
+    /// is a leaf. Useful for visualizing the BVH.
+    pub fn debug_volumes(&self, max_depth: usize) -> Vec<(AABB, usize, bool)> {
+        let mut retval = vec![];
+        let mut stack = vec![];
+        if self.root_id != INVALID_BVH_NODE_ID {
+            stack.push((self.root_id, 0));
+        }
+
+        while let Some((index, depth)) = stack.pop() {
+            let node = self.node_at(index);
+            retval.push((node.volume, depth, node.is_leaf()));
+
+            if depth < max_depth {
+                if node.left != INVALID_BVH_NODE_ID {
+                    stack.push((node.left, depth + 1));
+                }
+                if node.right != INVALID_BVH_NODE_ID {
+                    stack.push((node.right, depth + 1));
+                }
+            }
+        }
+        retval
+    }
+
     /// Print a graphviz representation of the BVH at the current moment
     pub fn walk_tree(&self) {
         let mut stack = vec![];
@@ -425,6 +534,27 @@ impl<Object: Copy + Clone> BVH<Object> {
     }
 }
 
+impl PartialEq for NearestCandidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for NearestCandidate {}
+
+impl PartialOrd for NearestCandidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NearestCandidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, since `BinaryHeap` pops the greatest element first
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
 impl<Object: Copy + Clone> BVHNode<Object> {
     fn is_leaf(&self) -> bool {
         self.left == INVALID_BVH_NODE_ID && self.right == INVALID_BVH_NODE_ID
@@ -455,6 +585,30 @@ impl<'a, Object: Copy + Clone> Iterator for BVHFrustumIterator<'a, Object> {
     }
 }
 
+impl<'a, Object: Copy + Clone> Iterator for BVHAABBIterator<'a, Object> {
+    type Item = Object;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(current_id) = self.stack.pop() {
+            let current_node = self.bvh.node_at(current_id);
+            if !current_node.volume.intersects(self.aabb) {
+                continue;
+            }
+
+            if current_node.left != INVALID_BVH_NODE_ID {
+                self.stack.push(current_node.left);
+            }
+            if current_node.right != INVALID_BVH_NODE_ID {
+                self.stack.push(current_node.right);
+            }
+            if let Some(object) = current_node.object {
+                return Some(object);
+            }
+        }
+        None
+    }
+}
+
 impl<'a, Object: Copy + Clone> Iterator for BVHSphereIterator<'a, Object> {
     type Item = Object;
 
@@ -1,7 +1,13 @@
 //! This module implements the Camera structure. Cameras can either be perspective (typical for 3D) or orthographic
 //! (typical for 2D)
+//!
+//! The world is right-handed and Z-up: the terrain's height is along Z, and X and Y run along the ground. Assets
+//! authored Y-up can be brought in by rotating them a quarter turn about X.
 
-use super::frustum::Frustum;
+use super::{aabb::AABB, frustum::Frustum};
+
+/// The up direction of the world
+pub const WORLD_UP: nalgebra_glm::Vec3 = nalgebra_glm::Vec3::new(0.0, 0.0, 1.0);
 
 #[derive(Debug, Copy, Clone)]
 /// Which kind of projection the camera uses.
@@ -61,6 +67,36 @@ impl Camera {
         retval
     }
 
+    /// Creates an orthographic camera at `center` looking along `direction`, whose view volume exactly encloses a
+    /// world-space AABB. Useful for fitting a shadow camera around the visible part of a scene.
+    pub fn ortho_fit_aabb(
+        center: nalgebra_glm::Vec3,
+        direction: nalgebra_glm::Vec3,
+        up: nalgebra_glm::Vec3,
+        aabb: &AABB,
+    ) -> Self {
+        let lookat = center + direction;
+        let view_matrix = nalgebra_glm::look_at(&center, &lookat, &up);
+
+        // Every corner has to be transformed, since the AABB won't be axis-aligned in view space
+        let aabb_view_space = AABB::from_points(
+            aabb.corners()
+                .iter()
+                .map(|corner| (view_matrix * corner.push(1.0)).xyz()),
+        );
+
+        // The camera looks down -Z in view space, so near and far are the negated Z bounds
+        let projection_kind = ProjectionKind::Orthographic {
+            left: aabb_view_space.min.x,
+            right: aabb_view_space.max.x,
+            bottom: aabb_view_space.min.y,
+            top: aabb_view_space.max.y,
+            near: -aabb_view_space.max.z,
+            far: -aabb_view_space.min.z,
+        };
+        Self::new(center, lookat, up, projection_kind)
+    }
+
     /// Retrieves the camera's view and projection matrices
     pub fn view_proj_matrices(&self) -> (nalgebra_glm::Mat4, nalgebra_glm::Mat4) {
         (self.view_matrix, self.proj_matrix)
@@ -120,6 +156,19 @@ impl Camera {
         self.regen_view_proj_matrices()
     }
 
+    /// Moves both the position and lookat of the camera, so that it keeps looking in the same direction
+    pub fn translate(&mut self, delta: nalgebra_glm::Vec3) {
+        self.position += delta;
+        self.lookat += delta;
+        self.regen_view_proj_matrices()
+    }
+
+    /// Moves the camera relative to the way it's facing, along its forward, right, and true up directions
+    pub fn move_local(&mut self, forward: f32, right: f32, up: f32) {
+        let delta = self.forward() * forward + self.right() * right + self.true_up() * up;
+        self.translate(delta)
+    }
+
     /// Retrieves the position of the camera
     pub fn position(&self) -> nalgebra_glm::Vec3 {
         self.position
@@ -134,4 +183,57 @@ impl Camera {
     pub fn up(&self) -> nalgebra_glm::Vec3 {
         self.up
     }
+
+    /// The unit direction the camera is looking in
+    pub fn forward(&self) -> nalgebra_glm::Vec3 {
+        (self.lookat - self.position).normalize()
+    }
+
+    /// The unit direction to the right of the camera
+    pub fn right(&self) -> nalgebra_glm::Vec3 {
+        self.forward().cross(&self.up).normalize()
+    }
+
+    /// The unit direction straight up from the camera's point of view. Unlike `up`, this is always perpendicular to
+    /// `forward`.
+    pub fn true_up(&self) -> nalgebra_glm::Vec3 {
+        self.right().cross(&self.forward())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basis_vectors_are_unit_length_and_orthogonal() {
+        // Level, tilted down steeply, and looking up, none of which are perpendicular to `up`
+        let lookats = [
+            nalgebra_glm::vec3(10.0, 0.0, 0.0),
+            nalgebra_glm::vec3(1.0, 2.0, -20.0),
+            nalgebra_glm::vec3(-3.0, -1.0, 4.0),
+        ];
+        for lookat in lookats {
+            let camera = Camera::new(
+                nalgebra_glm::zero(),
+                lookat,
+                WORLD_UP,
+                ProjectionKind::default(),
+            );
+            let (forward, right, up) = (camera.forward(), camera.right(), camera.true_up());
+            for v in [forward, right, up] {
+                assert!(
+                    (v.magnitude() - 1.0).abs() < 1e-5,
+                    "{:?} isn't unit length",
+                    v
+                );
+            }
+            assert!(forward.dot(&right).abs() < 1e-5);
+            assert!(forward.dot(&up).abs() < 1e-5);
+            assert!(right.dot(&up).abs() < 1e-5);
+            // Upright, and not mirrored
+            assert!(up.dot(&WORLD_UP) > 0.0);
+            assert!((right.cross(&forward) - up).magnitude() < 1e-5);
+        }
+    }
 }
@@ -0,0 +1,153 @@
+//! This module implements controllers that move a `Camera` around in common ways, so that scenes only have to wire up
+//! their input.
+//!
+//! Controllers assume the world is Z-up, like the terrain. See `WORLD_UP`.
+
+use std::f32::consts::FRAC_PI_2;
+
+use sdl2::keyboard::Scancode;
+
+use super::{
+    app::App,
+    camera::{Camera, WORLD_UP},
+};
+
+/// How close to straight up or straight down the pitch of a controller may get, to avoid the view flipping over
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+/// The maximum pitch of a first-person camera, 89 degrees
+const MAX_FLY_PITCH: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+#[derive(Debug, Copy, Clone)]
+/// Orbits the camera around a target point, like a model viewer
+pub struct OrbitController {
+    /// The point the camera orbits around and looks at
+    pub target: nalgebra_glm::Vec3,
+    /// How far the camera is from the target
+    pub distance: f32,
+    /// The angle around the Z axis, in radians
+    pub yaw: f32,
+    /// The angle above the XY plane, in radians
+    pub pitch: f32,
+}
+
+impl OrbitController {
+    const MIN_DISTANCE: f32 = 0.1;
+
+    /// Create a new orbit controller
+    pub fn new(target: nalgebra_glm::Vec3, distance: f32, yaw: f32, pitch: f32) -> Self {
+        Self {
+            target,
+            distance: distance.max(Self::MIN_DISTANCE),
+            yaw,
+            pitch: pitch.clamp(-MAX_PITCH, MAX_PITCH),
+        }
+    }
+
+    /// Places the camera on the sphere around the target, looking at the target
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.set_position(self.target + self.offset_dir() * self.distance);
+        camera.set_lookat(self.target);
+    }
+
+    /// Rotates around the target by a change in yaw and pitch, in radians
+    pub fn orbit(&mut self, dx: f32, dy: f32) {
+        self.yaw += dx;
+        self.pitch = (self.pitch + dy).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Moves towards (positive) or away from (negative) the target
+    pub fn zoom(&mut self, delta: f32) {
+        self.distance = (self.distance - delta).max(Self::MIN_DISTANCE);
+    }
+
+    /// Moves the target along the camera's right and up directions
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let forward = -self.offset_dir();
+        let right = nalgebra_glm::cross(&forward, &WORLD_UP).normalize();
+        let up = nalgebra_glm::cross(&right, &forward);
+        self.target += right * dx + up * dy;
+    }
+
+    /// The unit direction from the target to the camera
+    fn offset_dir(&self) -> nalgebra_glm::Vec3 {
+        nalgebra_glm::vec3(
+            self.pitch.cos() * self.yaw.cos(),
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+/// Flies the camera around first-person style, with WASD to move and the mouse to look around. Space and left shift
+/// move straight up and down.
+pub struct FlyController {
+    /// The angle around the Z axis, in radians
+    pub yaw: f32,
+    /// The angle above the XY plane, in radians
+    pub pitch: f32,
+    /// How fast the camera moves, in units per second
+    pub speed: f32,
+    /// How many radians the camera turns per pixel of mouse motion
+    pub sensitivity: f32,
+}
+
+impl FlyController {
+    /// Create a new fly controller
+    pub fn new(yaw: f32, pitch: f32, speed: f32) -> Self {
+        Self {
+            yaw,
+            pitch: pitch.clamp(-MAX_FLY_PITCH, MAX_FLY_PITCH),
+            speed,
+            sensitivity: 0.005,
+        }
+    }
+
+    /// Reads the keyboard and mouse from the app, and moves the camera accordingly
+    pub fn update(&mut self, camera: &mut Camera, app: &App, dt: f32) {
+        self.yaw -= app.mouse_vel.x * self.sensitivity;
+        self.pitch =
+            (self.pitch - app.mouse_vel.y * self.sensitivity).clamp(-MAX_FLY_PITCH, MAX_FLY_PITCH);
+
+        let forward = self.forward();
+        let right = self.right();
+        let up = WORLD_UP;
+
+        let mut movement = nalgebra_glm::Vec3::zeros();
+        let bindings = [
+            (Scancode::W, forward),
+            (Scancode::S, -forward),
+            (Scancode::D, right),
+            (Scancode::A, -right),
+            (Scancode::Space, up),
+            (Scancode::LShift, -up),
+        ];
+        for (scancode, direction) in bindings {
+            if app.keys[scancode as usize] {
+                movement += direction;
+            }
+        }
+        if movement != nalgebra_glm::Vec3::zeros() {
+            movement = movement.normalize() * self.speed * dt;
+        }
+
+        let position = camera.position() + movement;
+        camera.set_position(position);
+        camera.set_lookat(position + forward);
+    }
+
+    /// The unit direction the camera is looking
+    pub fn forward(&self) -> nalgebra_glm::Vec3 {
+        nalgebra_glm::vec3(
+            self.pitch.cos() * self.yaw.cos(),
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+        )
+    }
+
+    /// The unit direction to the right of the camera, parallel to the ground
+    pub fn right(&self) -> nalgebra_glm::Vec3 {
+        nalgebra_glm::cross(&self.forward(), &WORLD_UP).normalize()
+    }
+}
@@ -4,22 +4,65 @@ use hecs::{Entity, World};
 
 use super::{
     bvh::BVH,
-    perlin::{HeightMap, PerlinMap},
-    render_core::{ModelComponent, RenderContext},
+    camera::WORLD_UP,
+    mesh_data::MeshData,
+    objects::ObjectBuilder,
+    perlin::{FractalParams, HeightMap, NoiseKind, PerlinMap, EROSION_REACH},
+    render_core::{GeometryDataIndex, Mesh, RenderContext},
 };
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The kind of terrain at a point, based on its height, hydration, and temperature
+pub enum Biome {
+    Beach,
+    Grassland,
+    Forest,
+    Desert,
+    Tundra,
+    Snow,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Settings for classifying terrain into biomes, rivers, and sea. Hydration and temperature are both in the range
+/// [0, 1).
+pub struct BiomeParams {
+    /// Terrain lower than this is beach
+    pub beach_height: f32,
+    /// Cold terrain higher than this is snow
+    pub snow_height: f32,
+    /// Hot, dry terrain lower than this is desert
+    pub desert_height: f32,
+    /// Terrain colder than this is tundra or snow
+    pub cold_temperature: f32,
+    /// Terrain hotter than this can be desert
+    pub hot_temperature: f32,
+    /// Terrain drier than this can be desert
+    pub dry_hydration: f32,
+    /// Terrain wetter than this is forest
+    pub wet_hydration: f32,
+    /// How many rain drops are run down each chunk to find where rivers flow. 0 turns rivers off.
+    pub rain_drops: usize,
+    /// Terrain that more water flows over than this is drawn as a river
+    pub river_flow: f32,
+    /// The height of the sea's surface
+    pub sea_level: f32,
+}
+
 #[derive(Default)]
 /// A single chunk, with it's height map, position, and other info
 pub struct Chunk {
     map: PerlinMap,
     hydration: PerlinMap,
+    temperature: PerlinMap,
     chunk_width: usize,
     pos: nalgebra_glm::Vec2,
     generated: bool,
 
-    level_of_detail: f32,
+    fractal_params: FractalParams,
     seed: i32,
     amplitude: f32,
+    noise_kind: NoiseKind,
+    biome_params: BiomeParams,
 }
 
 #[derive(Default)]
@@ -29,9 +72,52 @@ pub struct ChunkedPerlinMap {
     map_width: usize,
     chunk_width: usize,
 
-    level_of_detail: f32,
+    fractal_params: FractalParams,
     seed: i32,
     amplitude: f32,
+    noise_kind: NoiseKind,
+    biome_params: BiomeParams,
+
+    // Whether the terrain texture has been set up for filtering at a distance
+    texture_filtered: bool,
+}
+
+/// The color that river beds are tinted toward
+const RIVER_COLOR: [f32; 4] = [0.25, 0.4, 0.55, 1.0];
+
+impl Biome {
+    /// The color that the terrain's materials are multiplied by in this biome
+    pub fn tint(&self) -> [f32; 4] {
+        match self {
+            Biome::Beach | Biome::Grassland => [1.0, 1.0, 1.0, 1.0],
+            Biome::Forest => [0.7, 0.85, 0.7, 1.0],
+            Biome::Desert => [1.0, 0.9, 0.7, 1.0],
+            Biome::Tundra => [0.85, 0.9, 0.9, 1.0],
+            Biome::Snow => [1.5, 1.5, 1.6, 1.0],
+        }
+    }
+
+    /// Whether the ground in this biome is sand
+    fn sandy(&self) -> bool {
+        matches!(self, Biome::Beach | Biome::Desert)
+    }
+}
+
+impl Default for BiomeParams {
+    fn default() -> Self {
+        Self {
+            beach_height: 0.5,
+            snow_height: 3.0,
+            desert_height: 2.0,
+            cold_temperature: 0.35,
+            hot_temperature: 0.6,
+            dry_hydration: 0.4,
+            wet_hydration: 0.6,
+            rain_drops: 256,
+            river_flow: 2.0,
+            sea_level: 0.0,
+        }
+    }
 }
 
 impl Chunk {
@@ -39,178 +125,242 @@ impl Chunk {
     pub fn new(
         chunk_width: usize,
         pos: nalgebra_glm::Vec2,
-        level_of_detail: f32,
+        fractal_params: FractalParams,
         seed: i32,
         amplitude: f32,
+        noise_kind: NoiseKind,
+        biome_params: BiomeParams,
     ) -> Self {
         Self {
             map: PerlinMap::new(chunk_width + 1),
             hydration: PerlinMap::new(chunk_width + 1),
+            temperature: PerlinMap::new(chunk_width + 1),
             chunk_width,
             pos,
             generated: false,
-            level_of_detail,
+            fractal_params,
             seed,
             amplitude,
+            noise_kind,
+            biome_params,
         }
     }
 
     /// Generate a new chunk
     pub fn generate(&mut self, renderer: &RenderContext, world: &mut World, bvh: &mut BVH<Entity>) {
         if !self.generated {
-            self.map.generate(
-                self.level_of_detail,
-                10,
-                self.seed,
-                self.amplitude,
-                self.pos,
-            );
-            self.hydration
-                .generate(self.level_of_detail, 2, self.seed, self.amplitude, self.pos);
-
-            self.map.create_bulge();
-            self.map.create_shelf(0.6, 0.4);
-
-            // self.map.erode(64, rand::Rng::gen(&mut rng));
+            self.generate_maps();
 
             let grass_texture = renderer.get_texture_id_from_name("grass").unwrap();
 
             let pos_with_z = nalgebra_glm::vec3(self.pos.x, self.pos.y, 0.0);
-            let (i, v, n, u) = self.create_mesh();
-            let grass_mesh = renderer.add_mesh_from_verts(i, vec![&v, &n, &u], None);
-            let chunk_entity = world.spawn((ModelComponent::new(
-                grass_mesh,
-                grass_texture,
-                pos_with_z,
-                nalgebra_glm::vec3(1.0, 1.0, 1.0),
-            ),));
-            bvh.insert(
-                chunk_entity,
-                renderer.get_mesh_aabb(grass_mesh).translate(pos_with_z),
-            );
+            let grass_mesh = renderer.add_mesh(self.create_mesh(), None);
+            ObjectBuilder::from_ids(grass_mesh, grass_texture)
+                .at(pos_with_z)
+                .spawn(world, renderer, bvh)
+                .unwrap();
 
             self.generated = true;
         }
     }
 
-    fn pos(&self) -> nalgebra_glm::Vec2 {
-        self.pos
-    }
-
-    fn height_nearest(&self, p: nalgebra_glm::Vec2) -> f32 {
-        self.map.height(p)
+    /// Generates the chunk's height, hydration, and temperature maps, and traces where rivers flow over the height map
+    fn generate_maps(&mut self) {
+        self.map = if self.biome_params.rain_drops > 0 {
+            // Rain that lands on other chunks flows into this one too, so erode a map that reaches as far as any drop
+            // that can get here, then keep this chunk's part of it. Drops are traced in world space, so the flow along
+            // a seam is the same in both chunks.
+            let margin = nalgebra_glm::vec2(EROSION_REACH as f32, EROSION_REACH as f32);
+            let mut watershed = generate_height_map(
+                self.chunk_width + 2 * EROSION_REACH,
+                self.fractal_params,
+                self.seed,
+                self.amplitude,
+                self.pos - margin,
+                self.noise_kind,
+            );
+            watershed.erode_world(
+                self.pos - margin,
+                self.chunk_width,
+                self.biome_params.rain_drops,
+                (self.seed as u64) << 32,
+            );
+            watershed.crop(EROSION_REACH, EROSION_REACH, self.chunk_width + 1)
+        } else {
+            generate_height_map(
+                self.chunk_width,
+                self.fractal_params,
+                self.seed,
+                self.amplitude,
+                self.pos,
+                self.noise_kind,
+            )
+        };
+        self.hydration.generate(
+            FractalParams {
+                octaves: 2,
+                ..self.fractal_params
+            },
+            self.seed,
+            self.amplitude,
+            self.pos,
+            NoiseKind::Fbm,
+        );
+        // Temperature changes slowly over the world, and shouldn't line up with hydration
+        self.temperature.generate(
+            FractalParams {
+                octaves: 2,
+                frequency: self.fractal_params.frequency / 4.0,
+                ..self.fractal_params
+            },
+            self.seed.wrapping_add(1),
+            1.0,
+            self.pos,
+            NoiseKind::Fbm,
+        );
     }
 
-    fn height_interpolated(&self, p: nalgebra_glm::Vec2) -> f32 {
-        self.map.get_z_interpolated(p)
+    fn pos(&self) -> nalgebra_glm::Vec2 {
+        self.pos
     }
 
-    fn normal(&self, p: nalgebra_glm::Vec2) -> nalgebra_glm::Vec3 {
-        self.map.get_normal(p)
+    /// Classifies the terrain at a point relative to this chunk
+    pub fn biome(&self, p: nalgebra_glm::Vec2) -> Biome {
+        let params = &self.biome_params;
+        let height = self.height_nearest(p);
+        let hydration = if self.amplitude != 0.0 {
+            self.hydration.height(p) / self.amplitude
+        } else {
+            0.0
+        };
+        let temperature = self.temperature.height(p);
+
+        let cold = temperature < params.cold_temperature;
+        if height < params.beach_height {
+            Biome::Beach
+        } else if cold && height > params.snow_height {
+            Biome::Snow
+        } else if cold {
+            Biome::Tundra
+        } else if temperature > params.hot_temperature
+            && hydration < params.dry_hydration
+            && height < params.desert_height
+        {
+            Biome::Desert
+        } else if hydration > params.wet_hydration {
+            Biome::Forest
+        } else {
+            Biome::Grassland
+        }
     }
 
-    fn flow(&self, p: nalgebra_glm::Vec2) -> f32 {
-        self.map.flow(p)
-    }
+    /// Creates the terrain mesh for this chunk. Each triangle's UVs point at one material in the terrain texture atlas,
+    /// and each vertex also gets blend weights for (grass, sand, cliff, water), which the built-in `3d-terrain` shader
+    /// blends materials by. Vertex colors are tinted by biome, and darkened toward water where rivers flow.
+    fn create_mesh(&self) -> Mesh {
+        let data = MeshData::from_heightmap(self, nalgebra_glm::zero(), self.chunk_width);
+        let positions = &data.attributes[GeometryDataIndex::Vertex as usize].0;
+        let normals = &data.attributes[GeometryDataIndex::Normal as usize].0;
+
+        let mut colors = Vec::with_capacity(positions.len() / 3 * 4);
+        let mut weights = Vec::with_capacity(positions.len() / 3 * 4);
+        for vertex in positions.chunks(3) {
+            let p = nalgebra_glm::vec2(vertex[0], vertex[1]);
+            let biome = self.biome(p);
+            let water = self.river(p);
+            let tint = biome.tint();
+            for i in 0..4 {
+                colors.push(nalgebra_glm::lerp_scalar(tint[i], RIVER_COLOR[i], water));
+            }
+            weights.extend(self.material_weights(p.x, p.y, biome, water));
+        }
 
-    fn create_mesh(&self) -> (Vec<u32>, Vec<f32>, Vec<f32>, Vec<f32>) {
-        let mut indices = Vec::<u32>::new();
-        let mut vertices = Vec::<f32>::new();
-        let mut normals = Vec::<f32>::new();
-        let mut uv = Vec::<f32>::new();
-
-        let mut i = 0;
-        for y in 0..self.chunk_width {
-            let y = y;
-            for x in 0..self.chunk_width {
-                let x = x;
-                // Left triangle |\
-                let offsets = vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)];
-                self.add_triangle(
-                    &mut indices,
-                    &mut vertices,
-                    &mut normals,
-                    &mut uv,
-                    x as f32,
-                    y as f32,
-                    &offsets,
-                    &mut i,
-                );
-
-                // Right triangle \|
-                let offsets = vec![(1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
-                self.add_triangle(
-                    &mut indices,
-                    &mut vertices,
-                    &mut normals,
-                    &mut uv,
-                    x as f32,
-                    y as f32,
-                    &offsets,
-                    &mut i,
-                );
+        // Triangles don't share vertices, so every three vertices are one triangle with one normal
+        let mut uv = Vec::with_capacity(positions.len());
+        for (triangle, normal) in positions.chunks(9).zip(normals.chunks(9)) {
+            let centroid = nalgebra_glm::vec2(
+                (triangle[0] + triangle[3] + triangle[6]) / 3.0,
+                (triangle[1] + triangle[4] + triangle[7]) / 3.0,
+            );
+            // 0 = steep
+            // 1 = flat
+            let dot_prod = nalgebra_glm::dot(
+                &nalgebra_glm::vec3(normal[0], normal[1], normal[2]),
+                &WORLD_UP,
+            );
+            let u_offset: f32 = if self.biome(centroid).sandy() {
+                3.0 / 9.0
+            } else if dot_prod < 0.9 {
+                5.0 / 9.0
+            } else {
+                0.0
+            };
+            let v_offset = 0.0;
+            for _ in 0..3 {
+                add_uv(&mut uv, u_offset, v_offset);
             }
         }
 
-        (indices, vertices, normals, uv)
+        Mesh::new_with_material_weights(
+            data.indices.clone(),
+            positions,
+            normals,
+            &uv,
+            &colors,
+            &weights,
+        )
     }
 
-    fn add_triangle(
-        &self,
-        indices: &mut Vec<u32>,
-        vertices: &mut Vec<f32>,
-        normals: &mut Vec<f32>,
-        uv: &mut Vec<f32>,
-        x: f32,
-        y: f32,
-        offsets: &Vec<(f32, f32)>,
-        i: &mut u32,
-    ) {
-        let mut sum_z = 0.0;
-        let tri_verts: Vec<nalgebra_glm::Vec3> = offsets
-            .iter()
-            .map(|(xo, yo)| {
-                let z = self.height_nearest(nalgebra_glm::vec2(x + xo, y + yo));
-                let mapval = nalgebra_glm::vec3(x + xo, y + yo, z);
-                sum_z += self.height_nearest(nalgebra_glm::vec2(x + xo, y + yo));
-                add_vertex(vertices, x + xo, y + yo, z);
-                indices.push(*i);
-                *i += 1;
-                mapval
-            })
-            .collect();
-
-        let edge1 = tri_verts[1] - tri_verts[0];
-        let edge2 = tri_verts[2] - tri_verts[0];
-        let normal = nalgebra_glm::cross(&edge1, &edge2).normalize();
-        for _ in 0..3 {
-            normals.push(normal.x);
-            normals.push(normal.y);
-            normals.push(normal.z);
-        }
-        // 0 = steep
-        // 1 = flat
-        let dot_prod = nalgebra_glm::dot(&normal, &nalgebra_glm::vec3(0.0, 0.0, 1.0));
+    /// How much of a river there is at a point, from 0 for dry land to 1 for the middle of a river
+    fn river(&self, p: nalgebra_glm::Vec2) -> f32 {
+        let threshold = self.biome_params.river_flow;
+        nalgebra_glm::smoothstep(threshold * 0.5, threshold, self.flow(p))
+    }
 
-        let avg_z = sum_z / 3.0;
-        let u_offset: f32 = if avg_z < 0.5 || (avg_z < 0.9 * dot_prod && 0.9 < dot_prod) {
-            3.0 / 9.0
-        } else if dot_prod < 0.9 {
-            5.0 / 9.0
-        } else {
-            0.0
+    /// The blend weights of grass, sand, cliff, and water at a vertex, which always sum to 1. The land materials follow
+    /// the same rules as the atlas offsets in `create_mesh`, but fade between materials instead of switching abruptly.
+    fn material_weights(&self, x: f32, y: f32, biome: Biome, water: f32) -> [f32; 4] {
+        let max = self.chunk_width as f32;
+        let height = |dx: f32, dy: f32| {
+            self.height_nearest(nalgebra_glm::vec2(
+                (x + dx).clamp(0.0, max),
+                (y + dy).clamp(0.0, max),
+            ))
         };
-        let v_offset = 0.0;
-        for _ in 0..3 {
-            add_uv(uv, u_offset, v_offset);
-        }
+        let normal = nalgebra_glm::vec3(
+            height(-1.0, 0.0) - height(1.0, 0.0),
+            height(0.0, -1.0) - height(0.0, 1.0),
+            2.0,
+        )
+        .normalize();
+        // 0 = steep
+        // 1 = flat
+        let flatness = normal.z;
+
+        let flat = nalgebra_glm::smoothstep(0.85, 0.95, flatness);
+        let sand = if biome.sandy() { 1.0 } else { 0.0 };
+        let cliff = (1.0 - sand) * (1.0 - flat);
+        let grass = 1.0 - sand - cliff;
+        let land = 1.0 - water;
+        [grass * land, sand * land, cliff * land, water]
     }
 }
 
-fn add_vertex(vertices: &mut Vec<f32>, x: f32, y: f32, z: f32) {
-    vertices.push(x);
-    vertices.push(y);
-    vertices.push(z);
+/// Generates the height map for a chunk at a world-space position. The map is one cell wider than the chunk, so that
+/// the last row and column are shared with the neighboring chunks' first row and column.
+fn generate_height_map(
+    chunk_width: usize,
+    fractal_params: FractalParams,
+    seed: i32,
+    amplitude: f32,
+    pos: nalgebra_glm::Vec2,
+    noise_kind: NoiseKind,
+) -> PerlinMap {
+    let mut map = PerlinMap::new(chunk_width + 1);
+    map.generate(fractal_params, seed, amplitude, pos, noise_kind);
+    map.create_bulge();
+    map.create_shelf(0.6, 0.4);
+    map
 }
 
 fn add_uv(uv: &mut Vec<f32>, x: f32, y: f32) {
@@ -224,19 +374,31 @@ impl ChunkedPerlinMap {
     pub fn new(
         map_width: usize,
         chunk_width: usize,
-        level_of_detail: f32,
+        fractal_params: FractalParams,
         seed: i32,
         amplitude: f32,
+        noise_kind: NoiseKind,
+        biome_params: BiomeParams,
     ) -> Self {
-        let chunks =
-            Self::generate_chunks(map_width, chunk_width, level_of_detail, seed, amplitude);
+        let chunks = Self::generate_chunks(
+            map_width,
+            chunk_width,
+            fractal_params,
+            seed,
+            amplitude,
+            noise_kind,
+            biome_params,
+        );
         Self {
             chunks,
             map_width,
             chunk_width,
-            level_of_detail,
+            fractal_params,
             seed,
             amplitude,
+            noise_kind,
+            biome_params,
+            texture_filtered: false,
         }
     }
 
@@ -248,6 +410,14 @@ impl ChunkedPerlinMap {
         world: &mut World,
         bvh: &mut BVH<Entity>,
     ) {
+        if !self.texture_filtered {
+            // Terrain is mostly seen at a distance and at grazing angles, where nearest sampling shimmers
+            if let Some(texture) = renderer.get_texture("grass") {
+                texture.enable_mipmaps_trilinear();
+            }
+            self.texture_filtered = true;
+        }
+
         for y in -3..4 {
             for x in -3..4 {
                 let chunk_offset = nalgebra_glm::vec2(x as f32, y as f32);
@@ -258,22 +428,46 @@ impl ChunkedPerlinMap {
         }
     }
 
-    /// Get the height of the map at a position _without_ storing the chunk
-    pub fn chunkless_height(&mut self, pos: nalgebra_glm::Vec2) -> f32 {
+    /// Get the height of the map at a position _without_ storing the chunk. This matches the height of the generated
+    /// chunk at the same position.
+    pub fn chunkless_height(&self, pos: nalgebra_glm::Vec2) -> f32 {
         let chunk_p =
             nalgebra_glm::floor(&(pos / self.chunk_width as f32)) * self.chunk_width as f32;
-        let mut map = PerlinMap::new(self.chunk_width);
-        map.generate(self.level_of_detail, 10, self.seed, self.amplitude, chunk_p);
-        let retval = map.get_z_interpolated(pos - chunk_p);
-        retval
+        let map = generate_height_map(
+            self.chunk_width,
+            self.fractal_params,
+            self.seed,
+            self.amplitude,
+            chunk_p,
+            self.noise_kind,
+        );
+        map.get_z_interpolated(pos - chunk_p)
+    }
+
+    /// The height of the sea's surface
+    pub fn sea_level(&self) -> f32 {
+        self.biome_params.sea_level
+    }
+
+    /// Whether the ground at a point is below the sea. The chunk at the point must have been generated.
+    pub fn is_underwater(&self, p: nalgebra_glm::Vec2) -> bool {
+        self.height_interpolated(p) < self.sea_level()
+    }
+
+    /// Classifies the terrain at a point. The chunk at the point must have been generated.
+    pub fn biome(&self, p: nalgebra_glm::Vec2) -> Biome {
+        let chunk = self.chunk_at(p);
+        chunk.biome(p - chunk.pos())
     }
 
     fn generate_chunks(
         map_width: usize,
         chunk_width: usize,
-        level_of_detail: f32,
+        fractal_params: FractalParams,
         seed: i32,
         amplitude: f32,
+        noise_kind: NoiseKind,
+        biome_params: BiomeParams,
     ) -> Vec<Chunk> {
         let mut chunks: Vec<Chunk> = vec![];
         let side_chunks = map_width / chunk_width;
@@ -282,9 +476,11 @@ impl ChunkedPerlinMap {
                 chunks.push(Chunk::new(
                     chunk_width,
                     nalgebra_glm::vec2((x * chunk_width) as f32, (y * chunk_width) as f32),
-                    level_of_detail,
+                    fractal_params,
                     seed,
                     amplitude,
+                    noise_kind,
+                    biome_params,
                 ));
             }
         }
@@ -304,6 +500,24 @@ impl ChunkedPerlinMap {
     }
 }
 
+impl HeightMap for Chunk {
+    fn height_nearest(&self, p: nalgebra_glm::Vec2) -> f32 {
+        self.map.height(p)
+    }
+
+    fn height_interpolated(&self, p: nalgebra_glm::Vec2) -> f32 {
+        self.map.get_z_interpolated(p)
+    }
+
+    fn normal(&self, p: nalgebra_glm::Vec2) -> nalgebra_glm::Vec3 {
+        self.map.get_normal(p)
+    }
+
+    fn flow(&self, p: nalgebra_glm::Vec2) -> f32 {
+        self.map.flow(p)
+    }
+}
+
 impl HeightMap for ChunkedPerlinMap {
     fn height_nearest(&self, p: nalgebra_glm::Vec2) -> f32 {
         let chunk = self.chunk_at(p);
@@ -325,3 +539,130 @@ impl HeightMap for ChunkedPerlinMap {
         chunk.flow(p - chunk.pos())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHUNK_WIDTH: usize = 16;
+    const SEED: i32 = 1234;
+    const AMPLITUDE: f32 = 1.0;
+
+    fn fractal_params() -> FractalParams {
+        FractalParams {
+            octaves: 4,
+            frequency: 0.05,
+            ..FractalParams::default()
+        }
+    }
+
+    // The test chunks are small, so the default amount of rain would be much heavier than in a real world
+    fn biome_params() -> BiomeParams {
+        BiomeParams {
+            rain_drops: 16,
+            ..BiomeParams::default()
+        }
+    }
+
+    fn chunk_at(pos: nalgebra_glm::Vec2) -> Chunk {
+        let mut chunk = Chunk::new(
+            CHUNK_WIDTH,
+            pos,
+            fractal_params(),
+            SEED,
+            AMPLITUDE,
+            NoiseKind::Fbm,
+            biome_params(),
+        );
+        chunk.generate_maps();
+        chunk
+    }
+
+    fn generated_chunk() -> Chunk {
+        let mut chunk = Chunk::new(
+            CHUNK_WIDTH,
+            nalgebra_glm::vec2(CHUNK_WIDTH as f32, -(CHUNK_WIDTH as f32)),
+            fractal_params(),
+            SEED,
+            AMPLITUDE,
+            NoiseKind::Ridged,
+            biome_params(),
+        );
+        chunk.generate_maps();
+        chunk
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_chunk() {
+        let first = generated_chunk();
+        let second = generated_chunk();
+
+        let mut total_flow = 0.0;
+        for i in 0..CHUNK_WIDTH * 2 {
+            for j in 0..CHUNK_WIDTH * 2 {
+                let p = nalgebra_glm::vec2(i as f32, j as f32) * 0.5;
+                assert_eq!(first.height_interpolated(p), second.height_interpolated(p));
+                assert_eq!(first.flow(p), second.flow(p));
+                assert_eq!(first.biome(p), second.biome(p));
+                total_flow += first.flow(p);
+            }
+        }
+        // Otherwise erosion didn't run, and the flows trivially match
+        assert!(total_flow > 0.0);
+    }
+
+    #[test]
+    fn neighboring_chunks_match_along_their_seam() {
+        let width = CHUNK_WIDTH as f32;
+        // Chosen so that rivers cross both seams
+        let origin = chunk_at(nalgebra_glm::vec2(width * 2.0, width));
+        let east = chunk_at(nalgebra_glm::vec2(width * 3.0, width));
+        let north = chunk_at(nalgebra_glm::vec2(width * 2.0, width * 2.0));
+
+        // Sample between the cells too, since that's where a seam would show up as a crack
+        for i in 0..CHUNK_WIDTH * 4 {
+            let t = i as f32 / 4.0;
+            let east_seam = (
+                origin.height_interpolated(nalgebra_glm::vec2(width, t)),
+                east.height_interpolated(nalgebra_glm::vec2(0.0, t)),
+            );
+            assert!(
+                (east_seam.0 - east_seam.1).abs() < 1e-5,
+                "east seam at {}: {:?}",
+                t,
+                east_seam
+            );
+
+            let north_seam = (
+                origin.height_interpolated(nalgebra_glm::vec2(t, width)),
+                north.height_interpolated(nalgebra_glm::vec2(t, 0.0)),
+            );
+            assert!(
+                (north_seam.0 - north_seam.1).abs() < 1e-5,
+                "north seam at {}: {:?}",
+                t,
+                north_seam
+            );
+        }
+
+        // Rivers have to carry on across the seams, so the cells along them have to have the same flow in both chunks
+        let mut seam_flow = 0.0;
+        for i in 0..=CHUNK_WIDTH {
+            let t = i as f32;
+            let east_seam = (
+                origin.flow(nalgebra_glm::vec2(width, t)),
+                east.flow(nalgebra_glm::vec2(0.0, t)),
+            );
+            assert_eq!(east_seam.0, east_seam.1, "east seam flow at {}", t);
+
+            let north_seam = (
+                origin.flow(nalgebra_glm::vec2(t, width)),
+                north.flow(nalgebra_glm::vec2(t, 0.0)),
+            );
+            assert_eq!(north_seam.0, north_seam.1, "north seam flow at {}", t);
+            seam_flow += east_seam.0 + north_seam.0;
+        }
+        // Otherwise no river crossed a seam, and the flows trivially match
+        assert!(seam_flow > 0.0);
+    }
+}
@@ -0,0 +1,141 @@
+//! This module implements a small immediate-mode UI for debug controls, like tuning terrain or lighting parameters
+//! live. Widgets are laid out top-to-bottom in a panel, and each call both draws the widget and reports how it was
+//! used this tick.
+//!
+//! Text is drawn with the renderer's current font, if one is set.
+
+use super::{app::App, rectangle::Rectangle};
+
+/// Lays out and draws debug widgets inside of a panel
+pub struct DebugUi<'a> {
+    app: &'a App,
+    panel: Rectangle,
+    cursor_y: f32,
+}
+
+impl<'a> DebugUi<'a> {
+    const ROW_HEIGHT: f32 = 24.0;
+    const PADDING: f32 = 4.0;
+
+    const PANEL_COLOR: nalgebra_glm::Vec4 = nalgebra_glm::Vec4::new(0.1, 0.1, 0.1, 0.8);
+    const WIDGET_COLOR: nalgebra_glm::Vec4 = nalgebra_glm::Vec4::new(0.3, 0.3, 0.3, 1.0);
+    const HOVER_COLOR: nalgebra_glm::Vec4 = nalgebra_glm::Vec4::new(0.4, 0.4, 0.4, 1.0);
+    const ACTIVE_COLOR: nalgebra_glm::Vec4 = nalgebra_glm::Vec4::new(0.3, 0.5, 0.8, 1.0);
+
+    /// Draws the background of a panel, and starts laying out widgets at its top
+    pub fn begin(app: &'a App, panel: Rectangle) -> Self {
+        let retval = Self {
+            app,
+            panel,
+            cursor_y: panel.pos.y + Self::PADDING,
+        };
+        retval.fill(panel, Self::PANEL_COLOR);
+        retval
+    }
+
+    /// Draws a line of text
+    pub fn label(&mut self, text: &str) {
+        let row = self.next_row();
+        self.text(row, text);
+    }
+
+    /// Draws a button, and returns whether it was clicked
+    pub fn button(&mut self, text: &str) -> bool {
+        let row = self.next_row();
+        let hovered = self.hovered(row);
+        let color = match (hovered, self.app.mouse_left_down) {
+            (true, true) => Self::ACTIVE_COLOR,
+            (true, false) => Self::HOVER_COLOR,
+            _ => Self::WIDGET_COLOR,
+        };
+        self.fill(row, color);
+        self.text(row, text);
+        hovered && self.app.mouse_left_clicked
+    }
+
+    /// Draws a checkbox, toggling `value` when it's clicked. Returns whether the value changed.
+    pub fn checkbox(&mut self, text: &str, value: &mut bool) -> bool {
+        let row = self.next_row();
+        let clicked = self.hovered(row) && self.app.mouse_left_clicked;
+        if clicked {
+            *value = !*value;
+        }
+
+        let box_size = row.size.y - Self::PADDING * 2.0;
+        let check_box = Rectangle::new(
+            row.pos.x + Self::PADDING,
+            row.pos.y + Self::PADDING,
+            box_size,
+            box_size,
+        );
+        let color = if *value {
+            Self::ACTIVE_COLOR
+        } else {
+            Self::WIDGET_COLOR
+        };
+        self.fill(check_box, color);
+        let label = Rectangle::new(
+            row.pos.x + box_size + Self::PADDING,
+            row.pos.y,
+            row.size.x - box_size - Self::PADDING,
+            row.size.y,
+        );
+        self.text(label, text);
+        clicked
+    }
+
+    /// Draws a slider between `min` and `max`, which sets `value` while dragged. Returns whether the value changed.
+    pub fn slider(&mut self, text: &str, value: &mut f32, min: f32, max: f32) -> bool {
+        let row = self.next_row();
+        let mut changed = false;
+        if self.hovered(row) && self.app.mouse_left_down && max > min {
+            let t = ((self.app.mouse_pos.x - row.pos.x) / row.size.x).clamp(0.0, 1.0);
+            let new_value = min + (max - min) * t;
+            changed = new_value != *value;
+            *value = new_value;
+        }
+
+        self.fill(row, Self::WIDGET_COLOR);
+        let t = if max > min {
+            ((*value - min) / (max - min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let filled = Rectangle::new(row.pos.x, row.pos.y, row.size.x * t, row.size.y);
+        self.fill(filled, Self::ACTIVE_COLOR);
+        self.text(row, &format!("{}: {:.3}", text, value));
+        changed
+    }
+
+    /// Moves the layout cursor down, and returns the rectangle of the row it moved past
+    fn next_row(&mut self) -> Rectangle {
+        let row = Rectangle::new(
+            self.panel.pos.x + Self::PADDING,
+            self.cursor_y,
+            self.panel.size.x - Self::PADDING * 2.0,
+            Self::ROW_HEIGHT,
+        );
+        self.cursor_y += Self::ROW_HEIGHT + Self::PADDING;
+        row
+    }
+
+    fn hovered(&self, rect: Rectangle) -> bool {
+        rect.contains_point(&self.app.mouse_pos)
+    }
+
+    fn fill(&self, rect: Rectangle, color: nalgebra_glm::Vec4) {
+        let renderer = &self.app.renderer;
+        let old_color = *renderer.color.borrow();
+        renderer.set_color(color);
+        renderer.fill_rect(rect);
+        renderer.set_color(old_color);
+    }
+
+    fn text(&self, rect: Rectangle, text: &str) {
+        let renderer = &self.app.renderer;
+        if renderer.font.borrow().is_none() {
+            return;
+        }
+        renderer.draw_text(rect.pos + nalgebra_glm::vec2(Self::PADDING, 0.0), text);
+    }
+}
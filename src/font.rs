@@ -65,6 +65,8 @@ impl Font {
 
     /// Draw text in a font to the screen
     pub fn draw(&self, pos: nalgebra_glm::Vec2, text: &str, renderer: &RenderContext) {
+        // The 2D camera maps one unit to one pixel, so snapping to whole pixels keeps glyphs from being resampled
+        let pos = pos.map(f32::round);
         let mut cursor = pos;
         for c in text.chars() {
             let c_byte: u8 = c as u8;
@@ -89,6 +91,28 @@ impl Font {
         }
     }
 
+    /// The size of the box that text would fill if it were drawn in this font
+    pub fn measure(&self, text: &str) -> nalgebra_glm::Vec2 {
+        let mut width: usize = 0;
+        let mut line_width: usize = 0;
+        let mut lines = 1;
+        for c in text.chars() {
+            if c == '\n' {
+                width = width.max(line_width);
+                line_width = 0;
+                lines += 1;
+                continue;
+            }
+            if !c.is_ascii_graphic() && !c.is_whitespace() {
+                continue;
+            }
+            line_width += self.get_glyph(c as u8).advance;
+        }
+        width = width.max(line_width);
+        let height = self.height + (lines - 1) * self.line_skip;
+        nalgebra_glm::vec2(width as f32, height as f32)
+    }
+
     fn pack_gylphs(&mut self, font: &sdl2::ttf::Font, renderer: &RenderContext) {
         let mut x_offset: usize = 0;
         let mut y_offset: usize = 0;
@@ -1,7 +1,7 @@
 //! This module defines the frustum data structure. A frustum is often used to represent the volume that is visible to
 //! a camera.
 
-use super::plane::Plane;
+use super::{aabb::AABB, plane::Plane};
 
 #[derive(Debug, Copy, Clone)]
 /// A frustum data structure
@@ -91,6 +91,11 @@ impl Frustum {
         self.corners
     }
 
+    /// A conservative AABB around the frustum, for cheap rejection before testing against its planes
+    pub fn bounding_aabb(&self) -> AABB {
+        AABB::from_points(self.corners())
+    }
+
     fn construct_plane(
         a: nalgebra_glm::Vec3,
         b: nalgebra_glm::Vec3,
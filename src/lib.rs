@@ -3,13 +3,18 @@ pub mod app;
 pub mod audio;
 pub mod bvh;
 pub mod camera;
+pub mod camera_controller;
 pub mod chunked_map;
+pub mod debug_ui;
 pub mod font;
 pub mod frustum;
+pub mod mesh_data;
+pub mod objects;
 pub mod opengl;
 pub mod perlin;
 pub mod physics;
 pub mod plane;
+pub mod post_process;
 pub mod ray;
 pub mod rectangle;
 pub mod render2d;
@@ -17,3 +22,6 @@ pub mod render3d;
 pub mod render_core;
 pub mod shadow_map;
 pub mod sphere;
+pub mod task_pool;
+pub mod text_field;
+pub mod transform;
@@ -0,0 +1,487 @@
+//! This module contains `MeshData`, the CPU-side copy of a mesh's geometry, and a binary format for caching it on disk.
+//!
+//! Generating meshes, like terrain chunks, can be slow. A host app can save the result with `Mesh::to_data` and
+//! `MeshData::to_bytes`, and later load it with `MeshData::from_bytes` and `RenderContext::add_mesh_from_data`.
+//!
+//! `MeshData::from_heightmap` builds the geometry of a height field, for terrain and anything else that meshes one.
+//! `MeshData::from_obj` parses an OBJ file, without touching the GPU.
+//!
+//! All numbers in the format are little-endian. The format is:
+//! - The magic bytes `APMESH`, and a `u32` version
+//! - The primitive as a `u32`, whether the mesh is indexed as a `u8`, and the AABB's min and max as six `f32`s
+//! - The number of indices as a `u32`, followed by the indices as `u32`s
+//! - The number of attributes as a `u32`. Each attribute is its number of components per vertex as a `u32`, then its
+//!   number of floats as a `u32`, followed by the floats.
+//! - Whether the mesh has tangents as a `u8`, and if so, the number of floats as a `u32`, followed by the floats
+
+use std::collections::HashMap;
+
+use obj::raw::{object::Polygon, parse_obj};
+
+use super::{aabb::AABB, perlin::HeightMap};
+
+/// The position, texture coordinate, and normal indices of a corner of an OBJ face
+type ObjCorner = (usize, Option<usize>, Option<usize>);
+
+const MAGIC: &[u8] = b"APMESH";
+const VERSION: u32 = 1;
+
+/// The geometry of a mesh, without any of the VRAM buffers
+#[derive(Clone, Debug)]
+pub struct MeshData {
+    pub indices: Vec<u32>,
+    /// The values of each vertex attribute, in location order, along with how many components each vertex has
+    pub attributes: Vec<(Vec<f32>, i32)>,
+    /// Tangents for normal mapping, with four components per vertex
+    pub tangents: Option<Vec<f32>>,
+    pub indexed: bool,
+    pub primitive: gl::types::GLenum,
+    pub aabb: AABB,
+}
+
+impl MeshData {
+    /// Creates a flat-shaded grid of triangles over a height field, `width` cells on a side. The vertex at grid
+    /// coordinates (x, y) is sampled from the height map at `origin + (x, y)`, and placed there with its height as Z.
+    /// Triangles don't share vertices, so that each gets its own face normal. Texture coordinates run from 0 to 1
+    /// across the grid.
+    pub fn from_heightmap(hm: &dyn HeightMap, origin: nalgebra_glm::Vec2, width: usize) -> Self {
+        // Two triangles per cell, |\ and \|
+        const TRIANGLE_OFFSETS: [[(f32, f32); 3]; 2] = [
+            [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            [(1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+        ];
+
+        let vertex_count = width * width * 6;
+        let mut indices = Vec::with_capacity(vertex_count);
+        let mut positions = Vec::with_capacity(vertex_count * 3);
+        let mut normals = Vec::with_capacity(vertex_count * 3);
+        let mut uvs = Vec::with_capacity(vertex_count * 3);
+        for y in 0..width {
+            for x in 0..width {
+                for offsets in TRIANGLE_OFFSETS {
+                    let corners = offsets.map(|(xo, yo)| {
+                        let p = origin + nalgebra_glm::vec2(x as f32 + xo, y as f32 + yo);
+                        nalgebra_glm::vec3(p.x, p.y, hm.height_nearest(p))
+                    });
+                    let normal = (corners[1] - corners[0])
+                        .cross(&(corners[2] - corners[0]))
+                        .normalize();
+                    for (corner, (xo, yo)) in corners.iter().zip(offsets) {
+                        indices.push(indices.len() as u32);
+                        positions.extend_from_slice(corner.as_slice());
+                        normals.extend_from_slice(normal.as_slice());
+                        uvs.extend_from_slice(&[
+                            (x as f32 + xo) / width as f32,
+                            (y as f32 + yo) / width as f32,
+                            0.0,
+                        ]);
+                    }
+                }
+            }
+        }
+
+        let aabb = AABB::from_points(
+            positions
+                .chunks(3)
+                .map(|p| nalgebra_glm::vec3(p[0], p[1], p[2])),
+        );
+        Self {
+            indices,
+            attributes: vec![(positions, 3), (normals, 3), (uvs, 3)],
+            tangents: None,
+            indexed: true,
+            primitive: gl::TRIANGLES,
+            aabb,
+        }
+    }
+
+    /// Parses the contents of an OBJ file. Corners that share a position, texture coordinate, and normal are merged
+    /// into one vertex. Faces without normals get a flat normal computed from their edges, and corners without texture
+    /// coordinates get zeros.
+    pub fn from_obj(obj_file_data: &[u8]) -> Result<Self, String> {
+        let raw = parse_obj(obj_file_data).map_err(|e| e.to_string())?;
+
+        let position_at = |i: usize| {
+            raw.positions
+                .get(i)
+                .map(|&(x, y, z, _)| nalgebra_glm::vec3(x, y, z))
+                .ok_or(format!("OBJ face refers to missing position {}", i))
+        };
+        let uv_at = |i: usize| {
+            raw.tex_coords
+                .get(i)
+                .map(|&(u, v, w)| nalgebra_glm::vec3(u, v, w))
+                .ok_or(format!(
+                    "OBJ face refers to missing texture coordinate {}",
+                    i
+                ))
+        };
+        let normal_at = |i: usize| {
+            raw.normals
+                .get(i)
+                .map(|&(x, y, z)| nalgebra_glm::vec3(x, y, z))
+                .ok_or(format!("OBJ face refers to missing normal {}", i))
+        };
+
+        let mut positions = vec![];
+        let mut normals = vec![];
+        let mut uvs = vec![];
+        let mut indices = vec![];
+        // Keyed by the corner's position, texture coordinate, and normal indices. Corners with a computed flat normal
+        // are also keyed by their face, so that they're only shared within it.
+        let mut vertex_ids: HashMap<(ObjCorner, Option<usize>), u32> = HashMap::new();
+
+        for (face, polygon) in raw.polygons.iter().enumerate() {
+            let corners: Vec<ObjCorner> = match polygon {
+                Polygon::P(c) => c.iter().map(|&p| (p, None, None)).collect(),
+                Polygon::PT(c) => c.iter().map(|&(p, t)| (p, Some(t), None)).collect(),
+                Polygon::PN(c) => c.iter().map(|&(p, n)| (p, None, Some(n))).collect(),
+                Polygon::PTN(c) => c.iter().map(|&(p, t, n)| (p, Some(t), Some(n))).collect(),
+            };
+            if corners.len() < 3 {
+                continue;
+            }
+
+            let (a, b, c) = (
+                position_at(corners[0].0)?,
+                position_at(corners[1].0)?,
+                position_at(corners[2].0)?,
+            );
+            let face_normal = (b - a)
+                .cross(&(c - a))
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(nalgebra_glm::zero);
+
+            let mut ids = Vec::with_capacity(corners.len());
+            for (p, t, n) in corners {
+                let key = ((p, t, n), if n.is_none() { Some(face) } else { None });
+                let id = match vertex_ids.get(&key) {
+                    Some(id) => *id,
+                    None => {
+                        let position = position_at(p)?;
+                        let uv = t.map(uv_at).transpose()?.unwrap_or_else(nalgebra_glm::zero);
+                        let normal = n.map(normal_at).transpose()?.unwrap_or(face_normal);
+                        positions.extend_from_slice(position.as_slice());
+                        uvs.extend_from_slice(uv.as_slice());
+                        normals.extend_from_slice(normal.as_slice());
+
+                        let id = vertex_ids.len() as u32;
+                        vertex_ids.insert(key, id);
+                        id
+                    }
+                };
+                ids.push(id);
+            }
+
+            // Split polygons with more than three corners into a fan of triangles
+            for i in 1..ids.len() - 1 {
+                indices.extend_from_slice(&[ids[0], ids[i], ids[i + 1]]);
+            }
+        }
+
+        let aabb = AABB::from_points(
+            positions
+                .chunks(3)
+                .map(|p| nalgebra_glm::vec3(p[0], p[1], p[2])),
+        );
+        Ok(Self {
+            indices,
+            attributes: vec![(positions, 3), (normals, 3), (uvs, 3)],
+            tangents: None,
+            indexed: true,
+            primitive: gl::TRIANGLES,
+            aabb,
+        })
+    }
+
+    /// Encodes the mesh data in the binary cache format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.primitive.to_le_bytes());
+        bytes.push(self.indexed as u8);
+        for value in self.aabb.min.iter().chain(self.aabb.max.iter()) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.indices.len() as u32).to_le_bytes());
+        for index in &self.indices {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.attributes.len() as u32).to_le_bytes());
+        for (values, components) in &self.attributes {
+            bytes.extend_from_slice(&(*components as u32).to_le_bytes());
+            write_floats(&mut bytes, values);
+        }
+
+        match &self.tangents {
+            Some(tangents) => {
+                bytes.push(1);
+                write_floats(&mut bytes, tangents);
+            }
+            None => bytes.push(0),
+        }
+        bytes
+    }
+
+    /// Decodes mesh data from the binary cache format. Returns an error if the bytes are truncated, or are from a
+    /// different version of the format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes, offset: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err("not a mesh cache".to_string());
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!(
+                "mesh cache version {} is not supported, expected {}",
+                version, VERSION
+            ));
+        }
+
+        let primitive = reader.u32()?;
+        let indexed = reader.u8()? != 0;
+        let min = nalgebra_glm::vec3(reader.f32()?, reader.f32()?, reader.f32()?);
+        let max = nalgebra_glm::vec3(reader.f32()?, reader.f32()?, reader.f32()?);
+
+        let index_count = reader.u32()? as usize;
+        let indices = (0..index_count)
+            .map(|_| reader.u32())
+            .collect::<Result<Vec<u32>, String>>()?;
+
+        let attribute_count = reader.u32()? as usize;
+        // The count isn't trusted to preallocate with, since a corrupt file could ask for far more than it holds
+        let mut attributes = vec![];
+        for _ in 0..attribute_count {
+            let components = reader.u32()? as i32;
+            attributes.push((reader.floats()?, components));
+        }
+
+        let tangents = match reader.u8()? {
+            0 => None,
+            _ => Some(reader.floats()?),
+        };
+
+        Ok(Self {
+            indices,
+            attributes,
+            tangents,
+            indexed,
+            primitive,
+            aabb: AABB::from_min_max(min, max),
+        })
+    }
+}
+
+fn write_floats(bytes: &mut Vec<u8>, values: &[f32]) {
+    bytes.extend_from_slice(&(values.len() as u32).to_le_bytes());
+    for value in values {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Reads little-endian values from the front of a byte slice
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.offset + len;
+        let slice = self
+            .bytes
+            .get(self.offset..end)
+            .ok_or("mesh cache is truncated")?;
+        self.offset = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn floats(&mut self) -> Result<Vec<f32>, String> {
+        let len = self.u32()? as usize;
+        (0..len).map(|_| self.f32()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE_POSITIONS: &str = "
+v -1 -1 -1
+v 1 -1 -1
+v 1 1 -1
+v -1 1 -1
+v -1 -1 1
+v 1 -1 1
+v 1 1 1
+v -1 1 1
+";
+
+    /// The cube's six faces as quads, wound counter-clockwise from outside, each with `normal` appended to its corners
+    fn cube_faces(normals: [&str; 6]) -> String {
+        const FACES: [[usize; 4]; 6] = [
+            [1, 4, 3, 2],
+            [5, 6, 7, 8],
+            [1, 2, 6, 5],
+            [3, 4, 8, 7],
+            [1, 5, 8, 4],
+            [2, 3, 7, 6],
+        ];
+        FACES
+            .iter()
+            .zip(normals)
+            .map(|(face, normal)| {
+                let corners: Vec<String> =
+                    face.iter().map(|p| format!("{}{}", p, normal)).collect();
+                format!("f {}\n", corners.join(" "))
+            })
+            .collect()
+    }
+
+    fn vertex_count(data: &MeshData) -> usize {
+        data.attributes[0].0.len() / 3
+    }
+
+    #[test]
+    fn cube_with_face_normals_has_four_vertices_per_face() {
+        let obj = format!(
+            "{}vn 0 0 -1\nvn 0 0 1\nvn 0 -1 0\nvn 0 1 0\nvn -1 0 0\nvn 1 0 0\n{}",
+            CUBE_POSITIONS,
+            cube_faces(["//1", "//2", "//3", "//4", "//5", "//6"])
+        );
+        let data = MeshData::from_obj(obj.as_bytes()).unwrap();
+        assert_eq!(vertex_count(&data), 24);
+        assert_eq!(data.indices.len(), 36);
+        assert_eq!(data.aabb.min, nalgebra_glm::vec3(-1.0, -1.0, -1.0));
+        assert_eq!(data.aabb.max, nalgebra_glm::vec3(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn cube_with_shared_normals_shares_its_corners() {
+        // One normal per position, as a smooth-shaded export would have
+        let obj = format!("{}vn 0 0 1\n{}", CUBE_POSITIONS, cube_faces(["//1"; 6]));
+        let data = MeshData::from_obj(obj.as_bytes()).unwrap();
+        assert_eq!(vertex_count(&data), 8);
+        assert_eq!(data.indices.len(), 36);
+    }
+
+    #[test]
+    fn computed_normals_are_only_shared_within_a_face() {
+        let obj = format!("{}{}", CUBE_POSITIONS, cube_faces([""; 6]));
+        let data = MeshData::from_obj(obj.as_bytes()).unwrap();
+        assert_eq!(vertex_count(&data), 24);
+        assert_eq!(data.indices.len(), 36);
+
+        // Every vertex's normal is its face's outward normal, so it points the same way as its position
+        let (positions, normals) = (&data.attributes[0].0, &data.attributes[1].0);
+        for (p, n) in positions.chunks(3).zip(normals.chunks(3)) {
+            let (p, n) = (
+                nalgebra_glm::vec3(p[0], p[1], p[2]),
+                nalgebra_glm::vec3(n[0], n[1], n[2]),
+            );
+            assert!(
+                (n.norm() - 1.0).abs() < 1e-6,
+                "normal {:?} isn't unit length",
+                n
+            );
+            assert!(
+                (p.dot(&n) - 1.0).abs() < 1e-6,
+                "normal {:?} doesn't face out from {:?}",
+                n,
+                p
+            );
+        }
+    }
+
+    /// A mesh with some of everything the cache format stores
+    fn cached_mesh() -> MeshData {
+        let obj = format!("{}{}", CUBE_POSITIONS, cube_faces([""; 6]));
+        let mut data = MeshData::from_obj(obj.as_bytes()).unwrap();
+        data.tangents = Some(
+            (0..vertex_count(&data) * 4)
+                .map(|i| i as f32 * 0.5)
+                .collect(),
+        );
+        data
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let data = cached_mesh();
+        let decoded = MeshData::from_bytes(&data.to_bytes()).unwrap();
+        assert_eq!(decoded.indices, data.indices);
+        assert_eq!(decoded.attributes, data.attributes);
+        assert_eq!(decoded.tangents, data.tangents);
+        assert_eq!(decoded.indexed, data.indexed);
+        assert_eq!(decoded.primitive, data.primitive);
+        assert_eq!(decoded.aabb.min, data.aabb.min);
+        assert_eq!(decoded.aabb.max, data.aabb.max);
+
+        let untangented = MeshData {
+            tangents: None,
+            ..data
+        };
+        let decoded = MeshData::from_bytes(&untangented.to_bytes()).unwrap();
+        assert_eq!(decoded.tangents, None);
+    }
+
+    #[test]
+    fn truncated_bytes_are_rejected() {
+        let bytes = cached_mesh().to_bytes();
+        for len in 0..bytes.len() {
+            assert!(
+                MeshData::from_bytes(&bytes[..len]).is_err(),
+                "{} of {} bytes decoded",
+                len,
+                bytes.len()
+            );
+        }
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut bytes = cached_mesh().to_bytes();
+        bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(MeshData::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn bogus_attribute_counts_are_rejected() {
+        let data = cached_mesh();
+        let mut bytes = data.to_bytes();
+        // The magic, version, primitive, indexed flag, AABB, and indices come before the attribute count
+        let offset = MAGIC.len() + 4 + 4 + 1 + 6 * 4 + 4 + data.indices.len() * 4;
+        assert_eq!(
+            bytes[offset..offset + 4],
+            (data.attributes.len() as u32).to_le_bytes()
+        );
+        bytes[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(MeshData::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn other_magic_is_rejected() {
+        let mut bytes = cached_mesh().to_bytes();
+        bytes[0] = b'X';
+        assert!(MeshData::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn missing_positions_are_rejected() {
+        assert!(MeshData::from_obj(b"v 0 0 0\nv 1 0 0\nf 1 2 3\n").is_err());
+    }
+}
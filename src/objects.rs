@@ -0,0 +1,179 @@
+//! This module contains helpers for spawning common kinds of objects into the world in one call.
+
+use hecs::{Entity, World};
+
+use super::{
+    bvh::BVH,
+    camera::{Camera, ProjectionKind, WORLD_UP},
+    physics::RigidBody,
+    render_core::{MeshId, ModelComponent, RenderContext, TextureId, DEFAULT_LAYER},
+    shadow_map::DirectionalLightSource,
+};
+
+/// The width and height of a light's shadow map when it isn't set, in texels
+const DEFAULT_SHADOW_MAP_SIZE: i32 = 2048;
+
+/// Where an object gets its mesh and texture from
+enum ModelSource {
+    Named(&'static str, &'static str),
+    Ids(MeshId, TextureId),
+}
+
+/// Assembles a renderable model entity, optionally with a rigid body. Models without a rigid body act as static
+/// colliders.
+pub struct ObjectBuilder {
+    source: ModelSource,
+    position: nalgebra_glm::Vec3,
+    scale: nalgebra_glm::Vec3,
+    outlined: bool,
+    alpha_cutout: Option<f32>,
+    layer_mask: u32,
+    mass: Option<f32>,
+}
+
+impl ObjectBuilder {
+    /// Starts building a model from the names of a mesh and texture already added to the renderer
+    pub fn model(mesh_name: &'static str, texture_name: &'static str) -> Self {
+        Self::from_source(ModelSource::Named(mesh_name, texture_name))
+    }
+
+    /// Starts building a model from a mesh and texture id
+    pub fn from_ids(mesh_id: MeshId, texture_id: TextureId) -> Self {
+        Self::from_source(ModelSource::Ids(mesh_id, texture_id))
+    }
+
+    /// Sets the position of the object
+    pub fn at(mut self, position: nalgebra_glm::Vec3) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Sets the scale of the object
+    pub fn scale(mut self, scale: nalgebra_glm::Vec3) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Draws an outline around the object
+    pub fn outlined(mut self) -> Self {
+        self.outlined = true;
+        self
+    }
+
+    /// Discards the parts of the object's texture whose alpha is below a threshold, for foliage
+    pub fn alpha_cutout(mut self, threshold: f32) -> Self {
+        self.alpha_cutout = Some(threshold);
+        self
+    }
+
+    /// Puts the object on the layers set in `mask`, instead of `DEFAULT_LAYER`
+    pub fn layers(mut self, mask: u32) -> Self {
+        self.layer_mask = mask;
+        self
+    }
+
+    /// Gives the object a rigid body with the given mass, so that it moves under physics
+    pub fn rigid_body(mut self, mass: f32) -> Self {
+        self.mass = Some(mass);
+        self
+    }
+
+    /// Spawns the object into the world and inserts it into the BVH
+    pub fn spawn(
+        self,
+        world: &mut World,
+        renderer: &RenderContext,
+        bvh: &mut BVH<Entity>,
+    ) -> Result<Entity, String> {
+        let (mesh_id, texture_id) = match self.source {
+            ModelSource::Named(mesh_name, texture_name) => (
+                renderer
+                    .get_mesh_id_from_name(mesh_name)
+                    .ok_or(format!("no mesh named {}", mesh_name))?,
+                renderer
+                    .get_texture_id_from_name(texture_name)
+                    .ok_or(format!("no texture named {}", texture_name))?,
+            ),
+            ModelSource::Ids(mesh_id, texture_id) => (mesh_id, texture_id),
+        };
+
+        let mut model = ModelComponent::new(mesh_id, texture_id, self.position, self.scale);
+        model.outlined = self.outlined;
+        model.alpha_cutout = self.alpha_cutout;
+        model.layer_mask = self.layer_mask;
+        let aabb = renderer.get_model_aabb(&model);
+
+        let entity = world.spawn((model,));
+        let bvh_node = bvh.insert(entity, aabb);
+        if let Some(mass) = self.mass {
+            world
+                .insert_one(entity, RigidBody::new(mass, bvh_node))
+                .unwrap();
+        }
+        Ok(entity)
+    }
+
+    fn from_source(source: ModelSource) -> Self {
+        Self {
+            source,
+            position: nalgebra_glm::zero(),
+            scale: nalgebra_glm::vec3(1.0, 1.0, 1.0),
+            outlined: false,
+            alpha_cutout: None,
+            layer_mask: DEFAULT_LAYER,
+            mass: None,
+        }
+    }
+}
+
+/// Assembles a light entity. The light is a `DirectionalLightSource` component, which the render systems borrow from
+/// the world each frame:
+/// ```ignore
+/// let mut light = world.get::<&mut DirectionalLightSource>(light_entity).unwrap();
+/// renderer.directional_light_system(&mut light, &world, &bvh);
+/// ```
+pub struct LightBuilder {
+    light_dir: nalgebra_glm::Vec3,
+    up: nalgebra_glm::Vec3,
+    shadow_map_size: i32,
+}
+
+impl LightBuilder {
+    /// Starts building a directional light, like the sun. `light_dir` points from the scene toward the light.
+    pub fn directional(light_dir: nalgebra_glm::Vec3) -> Self {
+        Self {
+            light_dir,
+            up: WORLD_UP,
+            shadow_map_size: DEFAULT_SHADOW_MAP_SIZE,
+        }
+    }
+
+    /// Sets the up vector of the light's shadow camera, instead of `WORLD_UP`. Needed for lights that shine straight
+    /// up or down.
+    pub fn up(mut self, up: nalgebra_glm::Vec3) -> Self {
+        self.up = up;
+        self
+    }
+
+    /// Sets the width and height of the light's shadow map, in texels
+    pub fn shadow_map_size(mut self, size: i32) -> Self {
+        self.shadow_map_size = size;
+        self
+    }
+
+    /// Creates the light's shadow map and spawns it into the world. The shadow camera is re-fit around the view every
+    /// frame by `directional_light_system`, so it starts at the origin.
+    pub fn spawn(self, world: &mut World) -> Entity {
+        let shadow_camera = Camera::new(
+            nalgebra_glm::zero(),
+            -self.light_dir,
+            self.up,
+            ProjectionKind::default(),
+        );
+        world.spawn((DirectionalLightSource::new(
+            shadow_camera,
+            self.light_dir,
+            self.shadow_map_size,
+        ),))
+    }
+}
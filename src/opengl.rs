@@ -178,6 +178,21 @@ pub fn create_program(
     Ok(shader_program)
 }
 
+/// Create a program with a vert, geometry, and frag shader. The geometry shader runs on each primitive after the vert
+/// shader, and can emit new primitives, for things like wireframes or visualizing normals. See
+/// `render_core::NORMALS_GEOM` for an example.
+pub fn create_program_geom(
+    vert_data: &'static str,
+    geom_data: &'static str,
+    frag_data: &'static str,
+) -> Result<Program, String> {
+    let vert_shader = Shader::from_source(&CString::new(vert_data).unwrap(), gl::VERTEX_SHADER)?;
+    let geom_shader = Shader::from_source(&CString::new(geom_data).unwrap(), gl::GEOMETRY_SHADER)?;
+    let frag_shader = Shader::from_source(&CString::new(frag_data).unwrap(), gl::FRAGMENT_SHADER)?;
+
+    Program::from_shaders(&[vert_shader, geom_shader, frag_shader])
+}
+
 /// OpenGL Vertex Buffer Object. Contains vertex data given as input to the vertex shader.
 pub struct Buffer<T> {
     pub id: GLuint,
@@ -247,17 +262,23 @@ impl<T> Drop for Buffer<T> {
 /// OpenGL Vertex Array Object
 pub struct Vao {
     pub id: GLuint,
+    components: GLint,
 }
 
 impl Vao {
-    /// Create a new VAO
+    /// Create a new VAO, for vertex attributes with three floats each
     pub fn gen() -> Self {
+        Self::gen_with_components(3)
+    }
+
+    /// Create a new VAO, for vertex attributes with `components` floats each
+    pub fn gen_with_components(components: GLint) -> Self {
         let mut id: GLuint = 0;
         unsafe {
             gl::GenVertexArrays(1, &mut id);
         }
         print_any_errors();
-        Vao { id }
+        Vao { id, components }
     }
 
     /// Bind and use this VAO
@@ -287,10 +308,10 @@ impl Vao {
         unsafe {
             gl::VertexAttribPointer(
                 loc,
-                3,
+                self.components,
                 gl::FLOAT,
                 gl::FALSE,
-                (3 * std::mem::size_of::<f32>()) as GLint,
+                self.components * std::mem::size_of::<f32>() as GLint,
                 null(),
             );
         }
@@ -319,6 +340,73 @@ impl Drop for Vao {
     }
 }
 
+/// An OpenGL Uniform Buffer Object. Holds a block of uniforms in one place, that can be shared by every program that
+/// declares the block, instead of being uploaded to each program separately.
+pub struct UniformBuffer {
+    pub id: GLuint,
+    binding: GLuint,
+}
+
+impl UniformBuffer {
+    /// Create a new uniform buffer of `size` bytes, attached to a binding point
+    pub fn new(binding: GLuint, size: usize) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, id);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                size as gl::types::GLsizeiptr,
+                null(),
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, id);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+        print_any_errors();
+        Self { id, binding }
+    }
+
+    /// Overwrite the start of the buffer. The data has to follow the block's layout, which is std140 for the blocks
+    /// used by apricot.
+    pub fn set_data(&self, data: &[f32]) {
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.id);
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                0,
+                std::mem::size_of_val(data) as gl::types::GLsizeiptr,
+                data.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+        print_any_errors();
+    }
+
+    /// Points a program's uniform block at this buffer. Returns whether the program declares the block.
+    pub fn bind_block(&self, program: u32, block_name: &str) -> bool {
+        let cname: CString = CString::new(block_name).expect("CString::new failed");
+        let index = unsafe { gl::GetUniformBlockIndex(program, cname.as_ptr()) };
+        if index == gl::INVALID_INDEX {
+            return false;
+        }
+        unsafe {
+            gl::UniformBlockBinding(program, index, self.binding);
+        }
+        print_any_errors();
+        true
+    }
+}
+
+impl Drop for UniformBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+        print_any_errors();
+    }
+}
+
 /// An OpenGL Uniform location
 pub struct Uniform {
     pub id: GLint,
@@ -335,6 +423,38 @@ impl Uniform {
         }
         Ok(Uniform { id: location })
     }
+
+    /// Upload a value to this uniform, for the currently used program
+    pub fn set(&self, value: &UniformValue) {
+        unsafe {
+            match value {
+                UniformValue::Int(x) => gl::Uniform1i(self.id, *x),
+                UniformValue::Float(x) => gl::Uniform1f(self.id, *x),
+                UniformValue::Vec2(v) => gl::Uniform2f(self.id, v.x, v.y),
+                UniformValue::Vec3(v) => gl::Uniform3f(self.id, v.x, v.y, v.z),
+                UniformValue::Vec4(v) => gl::Uniform4f(self.id, v.x, v.y, v.z, v.w),
+                UniformValue::Mat3(m) => {
+                    gl::UniformMatrix3fv(self.id, 1, gl::FALSE, &m.columns(0, 3)[0])
+                }
+                UniformValue::Mat4(m) => {
+                    gl::UniformMatrix4fv(self.id, 1, gl::FALSE, &m.columns(0, 4)[0])
+                }
+            }
+        }
+        print_any_errors();
+    }
+}
+
+/// A value that can be uploaded to a uniform
+#[derive(Copy, Clone, Debug)]
+pub enum UniformValue {
+    Int(i32),
+    Float(f32),
+    Vec2(nalgebra_glm::Vec2),
+    Vec3(nalgebra_glm::Vec3),
+    Vec4(nalgebra_glm::Vec4),
+    Mat3(nalgebra_glm::Mat3),
+    Mat4(nalgebra_glm::Mat4),
 }
 
 // TODO: Rename OpenGlTexture, and rename TextureId to Texture
@@ -361,6 +481,67 @@ impl Texture {
         texture
     }
 
+    /// Create an OpenGL texture from a PNG whose colors are in sRGB, so that they're sampled as linear colors
+    pub fn from_png_srgb(texture_filename: &'static str) -> Self {
+        let texture = Texture::new();
+        let path = Path::new(texture_filename);
+        texture.load_srgb(path).unwrap();
+        texture
+    }
+
+    /// Create an OpenGL texture from tightly packed 8-bit RGBA pixels, row by row from the top-left
+    pub fn from_rgba(width: u32, height: u32, pixels: &[u8]) -> Self {
+        assert_eq!(
+            pixels.len(),
+            (width * height * 4) as usize,
+            "pixel buffer does not match the texture size"
+        );
+        let texture = Texture::new();
+        texture.bind();
+        unsafe {
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            print_any_errors();
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            print_any_errors();
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            print_any_errors();
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            print_any_errors();
+
+            // Rows aren't padded, so don't assume they're aligned
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const _,
+            );
+            print_any_errors();
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+            print_any_errors();
+        }
+        texture
+    }
+
+    /// Create an OpenGL texture from a decoded image
+    pub fn from_image(img: &image::RgbaImage) -> Self {
+        Self::from_rgba(img.width(), img.height(), img.as_raw())
+    }
+
     /// Create an OpenGL texture from an SDL surface
     pub fn from_surface(surface: sdl2::surface::Surface) -> Self {
         let texture = Texture::new();
@@ -417,6 +598,16 @@ impl Texture {
 
     /// Load this texture into it's OpenGL slot
     pub fn load(&self, path: &Path) -> Result<(), ImageError> {
+        self.load_with_format(path, gl::RGBA)
+    }
+
+    /// Load this texture into it's OpenGL slot as `GL_SRGB8_ALPHA8`. Use this for textures that hold colors, but not
+    /// for textures that hold data like normals or heights.
+    pub fn load_srgb(&self, path: &Path) -> Result<(), ImageError> {
+        self.load_with_format(path, gl::SRGB8_ALPHA8)
+    }
+
+    fn load_with_format(&self, path: &Path, internal_format: GLenum) -> Result<(), ImageError> {
         self.bind();
 
         let img = image::open(path)?.into_rgba8();
@@ -441,7 +632,7 @@ impl Texture {
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RGBA as i32,
+                internal_format as i32,
                 img.width() as i32,
                 img.height() as i32,
                 0,
@@ -456,6 +647,31 @@ impl Texture {
         Ok(())
     }
 
+    /// Sample this texture with trilinear filtering, and anisotropic filtering where the driver supports it. This keeps
+    /// textures seen from far away or at steep angles from shimmering.
+    pub fn enable_mipmaps_trilinear(&self) {
+        self.bind();
+        unsafe {
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+            print_any_errors();
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR_MIPMAP_LINEAR as GLint,
+            );
+            print_any_errors();
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            print_any_errors();
+
+            if anisotropic_filtering_supported() {
+                let mut max_anisotropy: f32 = 1.0;
+                gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+                gl::TexParameterf(gl::TEXTURE_2D, TEXTURE_MAX_ANISOTROPY, max_anisotropy);
+                print_any_errors();
+            }
+        }
+    }
+
     /// Load this texture as a depth buffer
     pub fn load_depth_buffer(&self, width: i32, height: i32) {
         self.bind();
@@ -499,6 +715,51 @@ impl Texture {
         }
     }
 
+    /// Load this texture as an empty color buffer, which can be rendered into through an `Fbo`
+    pub fn load_color_buffer(&self, width: i32, height: i32) {
+        self.load_empty(width, height, gl::RGBA8, gl::UNSIGNED_BYTE);
+    }
+
+    /// Load this texture as an empty floating-point color buffer, for colors brighter than 1.0
+    pub fn load_hdr_color_buffer(&self, width: i32, height: i32) {
+        self.load_empty(width, height, gl::RGBA16F, gl::FLOAT);
+    }
+
+    fn load_empty(&self, width: i32, height: i32, internal_format: GLenum, data_type: GLenum) {
+        self.bind();
+
+        unsafe {
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                internal_format as GLint,
+                width,
+                height,
+                0,
+                gl::RGBA,
+                data_type,
+                std::ptr::null(),
+            );
+            print_any_errors();
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            print_any_errors();
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            print_any_errors();
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            print_any_errors();
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            print_any_errors();
+        }
+    }
+
     /// Method to call after binding
     pub fn post_bind(&self) {
         unsafe {
@@ -578,6 +839,42 @@ impl Default for Texture {
     }
 }
 
+/// Remembers the bound framebuffer and the viewport when it's created, and restores both when it's dropped. Passes
+/// that render into their own FBO, like shadow maps, hold one of these so they can run while another FBO is bound.
+#[must_use]
+pub struct FboScope {
+    framebuffer: GLint,
+    viewport: [GLint; 4],
+}
+
+impl FboScope {
+    /// Save the currently bound framebuffer and viewport
+    pub fn save() -> Self {
+        let mut framebuffer: GLint = 0;
+        let mut viewport: [GLint; 4] = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut framebuffer);
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        print_any_errors();
+        Self {
+            framebuffer,
+            viewport,
+        }
+    }
+}
+
+impl Drop for FboScope {
+    fn drop(&mut self) {
+        let [x, y, w, h] = self.viewport;
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer as GLuint);
+            gl::Viewport(x, y, w, h);
+        }
+        print_any_errors();
+    }
+}
+
 /// An OpenGL Frame Buffer Object
 pub struct Fbo {
     pub id: GLuint,
@@ -596,15 +893,196 @@ impl Fbo {
 
     /// Bind this FBO
     pub fn bind(&self) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.id) }
+        print_any_errors();
+    }
+
+    /// Bind this FBO until the returned scope is dropped, which restores the framebuffer and viewport that were current
+    /// before. Use this instead of `bind`/`unbind` when the pass may run while another FBO is bound.
+    pub fn bind_scoped(&self) -> FboScope {
+        let scope = FboScope::save();
+        self.bind();
+        scope
+    }
+
+    /// Unbind this FBO, binding the default framebuffer
+    pub fn unbind(&self) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0) }
+        print_any_errors();
+    }
+
+    /// Attach a texture as the color buffer of this FBO. Binds this FBO.
+    pub fn attach_color(&self, texture: &Texture) {
+        self.bind();
         unsafe {
-            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture.id,
+                0,
+            );
+            print_any_errors();
+            gl::DrawBuffer(gl::COLOR_ATTACHMENT0);
+            print_any_errors();
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            print_any_errors();
+        }
+    }
+
+    /// Attach a texture as the depth buffer of this FBO. Binds this FBO.
+    pub fn attach_depth(&self, texture: &Texture) {
+        self.bind();
+        unsafe {
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::TEXTURE_2D,
+                texture.id,
+                0,
+            );
         }
         print_any_errors();
     }
 
+    /// Checks that this FBO can be rendered to. Binds this FBO.
+    pub fn check_complete(&self) -> Result<(), String> {
+        self.bind();
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        print_any_errors();
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(format!(
+                "Framebuffer is not complete (status 0x{:X})",
+                status
+            ))
+        }
+    }
+}
+
+/// An OpenGL Frame Buffer Object with multisampled color and depth buffers. Multisampled buffers can't be sampled from
+/// in a shader, so they are resolved into a regular `Fbo` with `resolve_into` once rendering is done.
+pub struct MultisampleFbo {
+    fbo: Fbo,
+    color_rbo: GLuint,
+    depth_rbo: GLuint,
+    width: i32,
+    height: i32,
+    samples: i32,
+}
+
+impl MultisampleFbo {
+    /// Create a new multisampled Frame Buffer Object. The number of samples is clamped to what the driver supports.
+    pub fn new(width: i32, height: i32, samples: i32) -> Result<Self, String> {
+        let mut max_samples: GLint = 0;
+        unsafe { gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples) }
+        print_any_errors();
+        let samples = samples.clamp(1, max_samples.max(1));
+
+        let _scope = FboScope::save();
+        let fbo = Fbo::new();
+        let mut rbos: [GLuint; 2] = [0, 0];
+        unsafe {
+            gl::GenRenderbuffers(2, rbos.as_mut_ptr());
+            print_any_errors();
+
+            gl::BindRenderbuffer(gl::RENDERBUFFER, rbos[0]);
+            gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples, gl::RGBA8, width, height);
+            print_any_errors();
+            gl::BindRenderbuffer(gl::RENDERBUFFER, rbos[1]);
+            gl::RenderbufferStorageMultisample(
+                gl::RENDERBUFFER,
+                samples,
+                gl::DEPTH_COMPONENT24,
+                width,
+                height,
+            );
+            print_any_errors();
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+            fbo.bind();
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                rbos[0],
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::RENDERBUFFER,
+                rbos[1],
+            );
+            print_any_errors();
+        }
+
+        let retval = Self {
+            fbo,
+            color_rbo: rbos[0],
+            depth_rbo: rbos[1],
+            width,
+            height,
+            samples,
+        };
+        retval.fbo.check_complete().map(|_| retval)
+    }
+
+    /// Bind this FBO
+    pub fn bind(&self) {
+        self.fbo.bind();
+    }
+
+    /// Bind this FBO until the returned scope is dropped. See `Fbo::bind_scoped`.
+    pub fn bind_scoped(&self) -> FboScope {
+        self.fbo.bind_scoped()
+    }
+
     /// Unbind this FBO
     pub fn unbind(&self) {
-        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0) }
+        self.fbo.unbind();
+    }
+
+    /// The number of samples per pixel
+    pub fn samples(&self) -> i32 {
+        self.samples
+    }
+
+    /// The width and height of the buffers
+    pub fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    /// Averages the samples of the color buffer into the color attachment of `target`. Leaves the framebuffer binding
+    /// as it was.
+    pub fn resolve_into(&self, target: &Fbo) {
+        let _scope = FboScope::save();
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo.id);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target.id);
+            gl::BlitFramebuffer(
+                0,
+                0,
+                self.width,
+                self.height,
+                0,
+                0,
+                self.width,
+                self.height,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+            print_any_errors();
+        }
+    }
+}
+
+impl Drop for MultisampleFbo {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(2, [self.color_rbo, self.depth_rbo].as_ptr());
+            gl::DeleteFramebuffers(1, &self.fbo.id);
+        }
         print_any_errors();
     }
 }
@@ -615,6 +1093,26 @@ impl Default for Fbo {
     }
 }
 
+// Anisotropic filtering is only core in OpenGL 4.6, so these come from the extension
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+fn anisotropic_filtering_supported() -> bool {
+    let mut num_extensions: GLint = 0;
+    unsafe { gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut num_extensions) }
+    (0..num_extensions as GLuint).any(|i| {
+        let name = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+        if name.is_null() {
+            return false;
+        }
+        let name = unsafe { CStr::from_ptr(name as *const GLchar) };
+        matches!(
+            name.to_bytes(),
+            b"GL_EXT_texture_filter_anisotropic" | b"GL_ARB_texture_filter_anisotropic"
+        )
+    })
+}
+
 fn print_any_errors() {
     if let Some(error_message) = get_last_opengl_error() {
         println!("OpenGL Error:\n{}", error_message);
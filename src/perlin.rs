@@ -3,6 +3,9 @@
 use std::cmp::Ordering;
 
 use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::{camera::WORLD_UP, opengl::Texture};
 
 static HASH: [u8; 256] = [
     208, 34, 231, 213, 32, 248, 233, 56, 161, 78, 24, 140, 71, 48, 140, 254, 245, 255, 247, 247,
@@ -20,6 +23,31 @@ static HASH: [u8; 256] = [
     250, 1, 8, 198, 250, 209, 92, 222, 173, 21, 88, 102, 219,
 ];
 
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+/// The profile used to shape each octave of noise
+pub enum NoiseKind {
+    /// Plain fractal noise, good for rolling terrain
+    #[default]
+    Fbm,
+    /// Sharp creases where the noise crosses its midpoint, good for mountain ranges
+    Ridged,
+    /// Rounded bumps with creases in the valleys, good for hills and clouds
+    Billow,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Controls how octaves of noise are layered on top of each other
+pub struct FractalParams {
+    /// How many octaves of noise to sum
+    pub octaves: i32,
+    /// How much the amplitude is multiplied by for each successive octave
+    pub persistence: f32,
+    /// How much the frequency is multiplied by for each successive octave
+    pub lacunarity: f32,
+    /// The frequency of the first octave
+    pub frequency: f32,
+}
+
 #[derive(Default)]
 /// A 2D map of perlin noise
 pub struct PerlinMap {
@@ -33,6 +61,27 @@ struct Cell {
     pub flow: f32,
 }
 
+/// How many steps a rain drop takes before it evaporates
+const MAX_AGE: usize = 100;
+
+/// How far from where it lands a rain drop can affect a map, in cells. A drop moves √2 cells per step, rounded up to
+/// 1.5 here, and looks at the ground one step and one cell ahead of itself.
+pub const EROSION_REACH: usize = (MAX_AGE + 2) * 3 / 2 + 1;
+
+/// The ground that rain drops run down, which also records how much water flows over it
+trait Watershed {
+    fn ground_height(&self, p: nalgebra_glm::Vec2) -> f32;
+    fn ground_normal(&self, p: nalgebra_glm::Vec2) -> nalgebra_glm::Vec3;
+    fn add_flow(&mut self, p: nalgebra_glm::Vec2, flow: f32);
+}
+
+/// A map placed at a whole-numbered offset in the world, so that rain drops can be traced in world coordinates. A drop
+/// then takes exactly the same path over every map it crosses, since its coordinates are never rounded differently.
+struct WorldWindow<'a> {
+    map: &'a mut PerlinMap,
+    offset: (i64, i64),
+}
+
 struct Particle {
     pub age: usize,
 
@@ -48,6 +97,24 @@ pub trait HeightMap {
     fn height_interpolated(&self, p: nalgebra_glm::Vec2) -> f32;
     fn normal(&self, p: nalgebra_glm::Vec2) -> nalgebra_glm::Vec3;
     fn flow(&self, p: nalgebra_glm::Vec2) -> f32;
+
+    /// The height and normal of the ground under a body `radius` wide centered over `p`. The height is the highest
+    /// point under the body's center and edges, so that a body on a slope rests on the ground instead of sinking into
+    /// it, and the normal is averaged over the same points.
+    fn ground_contact(&self, p: nalgebra_glm::Vec2, radius: f32) -> (f32, nalgebra_glm::Vec3) {
+        const EDGE_DIRECTIONS: [(f32, f32); 4] = [(1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0)];
+
+        let mut height = self.height_interpolated(p);
+        let mut normal = self.normal(p);
+        if radius > 0.0 {
+            for (dx, dy) in EDGE_DIRECTIONS {
+                let edge = p + nalgebra_glm::vec2(dx, dy) * radius;
+                height = height.max(self.height_interpolated(edge));
+                normal += self.normal(edge);
+            }
+        }
+        (height, normal.normalize())
+    }
 }
 
 impl Particle {
@@ -61,16 +128,15 @@ impl Particle {
         }
     }
 
-    fn descend(&mut self, map: &mut PerlinMap) -> bool {
+    fn descend(&mut self, map: &mut impl Watershed) -> bool {
         loop {
             const EVAPORATION_RATE: f32 = 0.1;
-            const MAX_AGE: usize = 100;
 
             if self.age > MAX_AGE {
                 break;
             }
 
-            let grad = map.get_normal(self.pos.xy());
+            let grad = map.ground_normal(self.pos.xy());
 
             // Accelerate particle using classical mechanics
             let old_pos = self.pos;
@@ -82,15 +148,15 @@ impl Particle {
             self.vel.z -= 1.0; // gravity
             self.pos += self.vel;
             let mut flying = false;
-            if self.pos.z < map.height(self.pos.xy()) {
-                self.pos.z = map.height(self.pos.xy());
+            if self.pos.z < map.ground_height(self.pos.xy()) {
+                self.pos.z = map.ground_height(self.pos.xy());
                 flying = false;
             }
 
             // Update flow, momentum
             if !flying {
                 if self.pos.z >= 0.5 {
-                    map.incr_flow(old_pos.xy(), self.rain);
+                    map.add_flow(old_pos.xy(), self.rain);
                 } else {
                     self.rain = 1.0;
                 }
@@ -104,6 +170,46 @@ impl Particle {
     }
 }
 
+impl Watershed for PerlinMap {
+    fn ground_height(&self, p: nalgebra_glm::Vec2) -> f32 {
+        self.height(p)
+    }
+
+    fn ground_normal(&self, p: nalgebra_glm::Vec2) -> nalgebra_glm::Vec3 {
+        self.get_normal(p)
+    }
+
+    fn add_flow(&mut self, p: nalgebra_glm::Vec2, flow: f32) {
+        self.incr_flow(p, flow)
+    }
+}
+
+impl WorldWindow<'_> {
+    /// The map coordinates of the cell that a world-space point is in. Converting with integers is exact, unlike
+    /// subtracting the offset from the point.
+    fn cell(&self, p: nalgebra_glm::Vec2) -> nalgebra_glm::Vec2 {
+        nalgebra_glm::vec2(
+            (p.x.floor() as i64 - self.offset.0) as f32,
+            (p.y.floor() as i64 - self.offset.1) as f32,
+        )
+    }
+}
+
+impl Watershed for WorldWindow<'_> {
+    fn ground_height(&self, p: nalgebra_glm::Vec2) -> f32 {
+        self.map.height(self.cell(p))
+    }
+
+    fn ground_normal(&self, p: nalgebra_glm::Vec2) -> nalgebra_glm::Vec3 {
+        surface_normal(p, |corner| self.ground_height(corner))
+    }
+
+    fn add_flow(&mut self, p: nalgebra_glm::Vec2, flow: f32) {
+        let cell = self.cell(p);
+        self.map.incr_flow(cell, flow)
+    }
+}
+
 impl PerlinMap {
     /// Create a new 2D Perlin map
     pub fn new(map_width: usize) -> Self {
@@ -114,34 +220,34 @@ impl PerlinMap {
         retval
     }
 
-    /// Fill in the map with noise
+    /// Fill in the map with noise. The noise is sampled at `offset` plus each cell's local coordinate, so maps generated
+    /// at neighboring world-space offsets line up exactly along their shared edges. The noise only depends on its
+    /// arguments, so the same seed always generates the same map.
     pub fn generate(
         &mut self,
-        level_of_detail: f32,
-        depth: i32,
+        params: FractalParams,
         seed: i32,
         amplitude: f32,
         offset: nalgebra_glm::Vec2,
+        kind: NoiseKind,
     ) {
         assert!(self.cells.len() == 0);
         for y in 0..self.map_width {
             for x in 0..self.map_width {
                 self.cells.push(Cell {
-                    height: perlin2d(
-                        x as f32 + offset.x,
-                        y as f32 + offset.y,
-                        level_of_detail,
-                        depth,
-                        seed,
-                    ) * amplitude,
+                    height: perlin2d(x as f32 + offset.x, y as f32 + offset.y, params, seed, kind)
+                        * amplitude,
                     flow: 0.0,
                 });
             }
         }
     }
 
+    /// Simulates rain drops running down the map, recording how much water flows over each cell. The heights aren't
+    /// changed. The same seed always erodes the same way.
     pub fn erode(&mut self, total_particles: usize, seed: u64) {
-        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        // `StdRng`'s algorithm may change between versions of `rand`, so use an RNG that's guaranteed to be reproducible
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
 
         let mut checkpoint = total_particles / 10;
         for i in 0..total_particles {
@@ -165,6 +271,67 @@ impl PerlinMap {
         }
     }
 
+    /// Like `erode`, but for a map that covers part of an endless world, like a chunk's. `offset` is where the map's
+    /// first cell is in the world, and must be whole numbers. `drops_per_tile` drops fall on every `tile_width` square
+    /// of the world, seeded by `seed` and where the square is, and are traced in world coordinates. Maps that overlap
+    /// then record exactly the same flow where they overlap, except within `EROSION_REACH` of their edges, where drops
+    /// that landed outside of a map are missing.
+    pub fn erode_world(
+        &mut self,
+        offset: nalgebra_glm::Vec2,
+        tile_width: usize,
+        drops_per_tile: usize,
+        seed: u64,
+    ) {
+        let tile = tile_width as i64;
+        let start = (offset.x as i64, offset.y as i64);
+        let end = (
+            start.0 + self.map_width as i64 - 1,
+            start.1 + self.map_width as i64 - 1,
+        );
+        let mut window = WorldWindow {
+            map: self,
+            offset: start,
+        };
+
+        // Tiles are always visited in the same order, so that flow adds up in the same order on every map
+        for tile_y in start.1.div_euclid(tile)..=end.1.div_euclid(tile) {
+            for tile_x in start.0.div_euclid(tile)..=end.0.div_euclid(tile) {
+                let corner = nalgebra_glm::vec2((tile_x * tile) as f32, (tile_y * tile) as f32);
+                let mut rng = ChaCha8Rng::seed_from_u64(
+                    seed ^ (tile_x as u64).wrapping_mul(73856093)
+                        ^ (tile_y as u64).wrapping_mul(19349663),
+                );
+                for _ in 0..drops_per_tile {
+                    let landing = corner
+                        + nalgebra_glm::vec2(
+                            rng.gen_range(0.0..tile_width as f32),
+                            rng.gen_range(0.0..tile_width as f32),
+                        );
+                    // Drops that land off the map are skipped here too, since the map is 0 high there
+                    if window.ground_height(landing) < 0.5 {
+                        continue;
+                    }
+                    let mut drop = Particle::new(nalgebra_glm::vec3(landing.x, landing.y, 10.0));
+                    while drop.descend(&mut window) {}
+                }
+            }
+        }
+    }
+
+    /// Copies the `width` by `width` square of cells starting at cell (`x`, `y`) into a new map
+    pub fn crop(&self, x: usize, y: usize, width: usize) -> PerlinMap {
+        assert!(x + width <= self.map_width && y + width <= self.map_width);
+        let mut retval = PerlinMap::new(width);
+        for row in y..y + width {
+            let first = x + row * self.map_width;
+            retval
+                .cells
+                .extend_from_slice(&self.cells[first..first + width]);
+        }
+        retval
+    }
+
     pub fn cascade(&mut self, pos: nalgebra_glm::Vec2) {
         const MAX_DIFF: f32 = 0.9;
         const SETTLING: f32 = 0.8;
@@ -262,7 +429,7 @@ impl PerlinMap {
         let offset = p - origin;
 
         let ray_origin = nalgebra_glm::vec3(p.x, p.y, 10000.0);
-        let ray_direction = nalgebra_glm::vec3(0.0, 0.0, -1.0);
+        let ray_direction = -WORLD_UP;
 
         let offsets = if offset.y <= 1.0 - offset.x {
             // In bottom triangle
@@ -301,41 +468,14 @@ impl PerlinMap {
 
     /// Get the normal at a sub-pixel precise point
     pub fn get_normal(&self, p: nalgebra_glm::Vec2) -> nalgebra_glm::Vec3 {
-        assert!(!p.x.is_nan());
-        // The coordinates of the tile's origin (bottom left corner)
-        let origin = nalgebra_glm::floor(&p);
-
-        // Coordinates inside the tile. [0,1]
-        let offset = p - origin;
-
-        let offsets = if offset.y <= 1.0 - offset.x {
-            // In bottom triangle
-            [
-                nalgebra_glm::vec2(0.0, 0.0), // Contains the origin
-                nalgebra_glm::vec2(1.0, 0.0),
-                nalgebra_glm::vec2(0.0, 1.0),
-            ]
-        } else {
-            // In top triangle
-            [
-                nalgebra_glm::vec2(1.0, 0.0),
-                nalgebra_glm::vec2(1.0, 1.0), // Contains the anti-origin
-                nalgebra_glm::vec2(0.0, 1.0),
-            ]
-        };
-        let offsets: Vec<nalgebra_glm::Vec3> = offsets
-            .iter()
-            .map(|o| nalgebra_glm::vec3(origin.x + o.x, origin.y + o.y, self.height(origin + o)))
-            .collect();
-
-        tri_normal(offsets[0], offsets[1], offsets[2])
+        surface_normal(p, |corner| self.height(corner))
     }
 
     /// Get the dot product of the normal and up at a sub-pixel precise point
     pub fn get_dot_prod(&self, p: nalgebra_glm::Vec2) -> f32 {
         assert!(!p.x.is_nan());
 
-        nalgebra_glm::dot(&self.get_normal(p), &nalgebra_glm::vec3(0.0, 0.0, 1.0))
+        nalgebra_glm::dot(&self.get_normal(p), &WORLD_UP)
     }
 
     /// Create a bulge near the center of the map
@@ -360,6 +500,74 @@ impl PerlinMap {
         }
     }
 
+    /// Renders each cell's height as a greyscale image, from black for the lowest cell to white for the highest. The
+    /// map's +Y is up in the image.
+    pub fn to_image(&self) -> image::GrayImage {
+        let (min, max) = self.height_range();
+        let range = (max - min).max(f32::EPSILON);
+        let width = self.map_width as u32;
+        image::GrayImage::from_fn(width, width, |x, y| {
+            let cell = &self.cells[x as usize + (width - 1 - y) as usize * self.map_width];
+            image::Luma([((cell.height - min) / range * 255.0) as u8])
+        })
+    }
+
+    /// Renders the map as a color image for tuning terrain. Flat ground is green and steep slopes are brown, both
+    /// shaded by height, and cells that water flows through are tinted blue. The map's +Y is up in the image.
+    pub fn to_color_image(&self) -> image::RgbaImage {
+        const FLAT_COLOR: nalgebra_glm::Vec3 = nalgebra_glm::Vec3::new(0.35, 0.6, 0.25);
+        const STEEP_COLOR: nalgebra_glm::Vec3 = nalgebra_glm::Vec3::new(0.5, 0.4, 0.3);
+        const WATER_COLOR: nalgebra_glm::Vec3 = nalgebra_glm::Vec3::new(0.2, 0.4, 0.8);
+
+        let (min, max) = self.height_range();
+        let range = (max - min).max(f32::EPSILON);
+        let max_flow = self
+            .cells
+            .iter()
+            .fold(0.0, |max_flow: f32, cell| max_flow.max(cell.flow))
+            .max(f32::EPSILON);
+        // Normals sample the next row and column, so stay off of the far edges
+        let last_sample = self.map_width.saturating_sub(2) as f32;
+
+        let width = self.map_width as u32;
+        image::RgbaImage::from_fn(width, width, |x, y| {
+            let map_y = width - 1 - y;
+            let cell = &self.cells[x as usize + map_y as usize * self.map_width];
+            let sample = nalgebra_glm::vec2(x as f32, map_y as f32).map(|v| v.min(last_sample));
+            let flatness = nalgebra_glm::smoothstep(0.8, 0.95, self.get_dot_prod(sample));
+            let brightness = 0.5 + 0.5 * (cell.height - min) / range;
+            let water = (cell.flow / max_flow).sqrt();
+
+            let land = nalgebra_glm::lerp(&STEEP_COLOR, &FLAT_COLOR, flatness) * brightness;
+            let color = nalgebra_glm::lerp(&land, &WATER_COLOR, water);
+            image::Rgba([
+                (color.x * 255.0) as u8,
+                (color.y * 255.0) as u8,
+                (color.z * 255.0) as u8,
+                255,
+            ])
+        })
+    }
+
+    /// Uploads `to_image` as a texture, for previewing the map with `draw_sprite`
+    pub fn to_texture(&self) -> Texture {
+        Texture::from_image(&image::DynamicImage::ImageLuma8(self.to_image()).into_rgba8())
+    }
+
+    /// Uploads `to_color_image` as a texture, for previewing the map with `draw_sprite`
+    pub fn to_color_texture(&self) -> Texture {
+        Texture::from_image(&self.to_color_image())
+    }
+
+    /// The lowest and highest heights of any cell
+    fn height_range(&self) -> (f32, f32) {
+        self.cells
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), cell| {
+                (min.min(cell.height), max.max(cell.height))
+            })
+    }
+
     /// Normalize the map so that everything's sorta even
     pub fn normalize(&mut self) {
         let mut min = f32::MAX;
@@ -377,18 +585,42 @@ impl PerlinMap {
     }
 }
 
-fn perlin2d(x: f32, y: f32, freq: f32, depth: i32, seed: i32) -> f32 {
-    let mut xa = x * freq;
-    let mut ya = y * freq;
+impl Default for FractalParams {
+    fn default() -> Self {
+        Self {
+            octaves: 10,
+            persistence: 0.5,
+            lacunarity: 2.0,
+            frequency: 1.0,
+        }
+    }
+}
+
+impl NoiseKind {
+    /// Shapes a raw noise sample in the range [0, 256) according to the noise kind
+    fn shape(&self, n: f32) -> f32 {
+        // Signed noise in the range [-1, 1)
+        let signed = n / 128.0 - 1.0;
+        match self {
+            NoiseKind::Fbm => n,
+            NoiseKind::Ridged => (1.0 - signed.abs()) * 256.0,
+            NoiseKind::Billow => signed.abs() * 256.0,
+        }
+    }
+}
+
+fn perlin2d(x: f32, y: f32, params: FractalParams, seed: i32, kind: NoiseKind) -> f32 {
+    let mut xa = x * params.frequency;
+    let mut ya = y * params.frequency;
     let mut amp: f32 = 1.0;
     let mut fin: f32 = 0.0;
     let mut div: f32 = 256.0;
 
-    for _ in 0..depth {
-        fin += noise2d(xa, ya, seed) * amp;
-        xa *= 2.0;
-        ya *= 2.0;
-        amp *= 0.5;
+    for _ in 0..params.octaves {
+        fin += kind.shape(noise2d(xa, ya, seed)) * amp;
+        xa *= params.lacunarity;
+        ya *= params.lacunarity;
+        amp *= params.persistence;
         div += 256.0 * amp;
     }
 
@@ -396,8 +628,9 @@ fn perlin2d(x: f32, y: f32, freq: f32, depth: i32, seed: i32) -> f32 {
 }
 
 fn noise2d(x: f32, y: f32, seed: i32) -> f32 {
-    let x_int = x as i32;
-    let y_int = y as i32;
+    // Floor rather than truncate, so that the lattice doesn't mirror itself around zero
+    let x_int = x.floor() as i32;
+    let y_int = y.floor() as i32;
     let x_frac: f32 = x - (x_int as f32);
     let y_frac: f32 = y - (y_int as f32);
 
@@ -416,8 +649,8 @@ fn noise2d(x: f32, y: f32, seed: i32) -> f32 {
 }
 
 fn noise2(x: i32, y: i32, seed: i32) -> f32 {
-    let tmp = HASH[((y + seed) & 0xFF) as usize];
-    HASH[((tmp as i32 + x) & 0xFF) as usize] as f32
+    let tmp = HASH[(y.wrapping_add(seed) & 0xFF) as usize];
+    HASH[((tmp as i32).wrapping_add(x) & 0xFF) as usize] as f32
 }
 
 #[inline]
@@ -468,6 +701,41 @@ fn intersect(
     Some((intersection_point, t))
 }
 
+/// The normal of the triangle under `p`, in a grid of triangles with corners as high as `height` says
+fn surface_normal(
+    p: nalgebra_glm::Vec2,
+    height: impl Fn(nalgebra_glm::Vec2) -> f32,
+) -> nalgebra_glm::Vec3 {
+    assert!(!p.x.is_nan());
+    // The coordinates of the tile's origin (bottom left corner)
+    let origin = nalgebra_glm::floor(&p);
+
+    // Coordinates inside the tile. [0,1]
+    let offset = p - origin;
+
+    let offsets = if offset.y <= 1.0 - offset.x {
+        // In bottom triangle
+        [
+            nalgebra_glm::vec2(0.0, 0.0), // Contains the origin
+            nalgebra_glm::vec2(1.0, 0.0),
+            nalgebra_glm::vec2(0.0, 1.0),
+        ]
+    } else {
+        // In top triangle
+        [
+            nalgebra_glm::vec2(1.0, 0.0),
+            nalgebra_glm::vec2(1.0, 1.0), // Contains the anti-origin
+            nalgebra_glm::vec2(0.0, 1.0),
+        ]
+    };
+    let offsets: Vec<nalgebra_glm::Vec3> = offsets
+        .iter()
+        .map(|o| nalgebra_glm::vec3(origin.x + o.x, origin.y + o.y, height(origin + o)))
+        .collect();
+
+    tri_normal(offsets[0], offsets[1], offsets[2])
+}
+
 fn tri_normal(
     v0: nalgebra_glm::Vec3,
     v1: nalgebra_glm::Vec3,
@@ -478,3 +746,74 @@ fn tri_normal(
     let normal = nalgebra_glm::cross(&edge1, &edge2).normalize();
     normal
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP_WIDTH: usize = 33;
+    const SEED: i32 = 42;
+
+    fn fractal_params() -> FractalParams {
+        FractalParams {
+            octaves: 4,
+            frequency: 0.05,
+            ..FractalParams::default()
+        }
+    }
+
+    fn eroded_map() -> PerlinMap {
+        let mut map = PerlinMap::new(MAP_WIDTH);
+        map.generate(
+            fractal_params(),
+            SEED,
+            1.0,
+            nalgebra_glm::vec2(-16.0, 48.0),
+            NoiseKind::Fbm,
+        );
+        map.create_bulge();
+        map.erode(256, 7);
+        map
+    }
+
+    #[test]
+    fn noise_kinds_are_deterministic() {
+        let params = FractalParams {
+            octaves: 4,
+            frequency: 0.1,
+            ..FractalParams::default()
+        };
+        // Samples taken from a known-good build. A change here changes every generated world.
+        let expected = [
+            (NoiseKind::Fbm, 0.52243686, 0.5839288),
+            (NoiseKind::Ridged, 0.79776454, 0.7063143),
+            (NoiseKind::Billow, 0.1699774, 0.26142758),
+        ];
+        for (kind, first, second) in expected {
+            let samples = (
+                perlin2d(12.3, -4.7, params, SEED, kind),
+                perlin2d(-3.5, 20.25, params, SEED, kind),
+            );
+            assert!(
+                (samples.0 - first).abs() < 1e-6 && (samples.1 - second).abs() < 1e-6,
+                "{:?} sampled {:?}",
+                kind,
+                samples
+            );
+            assert_ne!(perlin2d(12.3, -4.7, params, SEED + 1, kind), samples.0);
+        }
+    }
+
+    #[test]
+    fn same_seed_erodes_the_same_map() {
+        let first = eroded_map();
+        let second = eroded_map();
+        for y in 0..MAP_WIDTH - 1 {
+            for x in 0..MAP_WIDTH - 1 {
+                let p = nalgebra_glm::vec2(x as f32 + 0.25, y as f32 + 0.5);
+                assert_eq!(first.get_z_interpolated(p), second.get_z_interpolated(p));
+                assert_eq!(first.flow(p), second.flow(p));
+            }
+        }
+    }
+}
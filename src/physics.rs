@@ -1,4 +1,16 @@
-//! NOTE: This isn't really implemented yet
+//! This module implements simple rigid body physics. Bodies are integrated with a fixed time step, and are pushed out
+//! of the static models they overlap with, sliding along their surfaces. Bodies that move far in one step are swept
+//! against static models, so that they can't tunnel through them.
+
+use hecs::{Entity, World};
+
+use super::{
+    aabb::AABB,
+    bvh::{BVHNodeId, BVH},
+    perlin::HeightMap,
+    ray::Ray,
+    render_core::{ModelComponent, RenderContext},
+};
 
 pub struct PositionComponent {
     pub pos: nalgebra_glm::Vec3,
@@ -7,3 +19,197 @@ pub struct PositionComponent {
 pub struct VelocityComponent {
     pub vel: nalgebra_glm::Vec3,
 }
+
+#[derive(Debug, Copy, Clone)]
+/// A body that moves under physics. Entities with a `ModelComponent` but no `RigidBody` are treated as static
+/// colliders.
+pub struct RigidBody {
+    pub velocity: nalgebra_glm::Vec3,
+    pub acceleration: nalgebra_glm::Vec3,
+    pub mass: f32,
+    /// Whether the body was resting on top of something during the last physics step
+    pub grounded: bool,
+    /// The BVH node of the body's model, which is moved along with the body
+    pub bvh_node: BVHNodeId,
+}
+
+#[derive(Debug, Copy, Clone)]
+/// Where a ray struck an entity
+pub struct RayHit {
+    pub entity: Entity,
+    pub point: nalgebra_glm::Vec3,
+    pub normal: nalgebra_glm::Vec3,
+    /// The distance along the ray, in units of the ray's direction
+    pub distance: f32,
+}
+
+impl RigidBody {
+    /// Create a new rigid body at rest
+    pub fn new(mass: f32, bvh_node: BVHNodeId) -> Self {
+        Self {
+            velocity: nalgebra_glm::zero(),
+            acceleration: nalgebra_glm::zero(),
+            mass,
+            grounded: false,
+            bvh_node,
+        }
+    }
+
+    /// Applies an instantaneous impulse to the body, changing it's velocity
+    pub fn apply_impulse(&mut self, impulse: nalgebra_glm::Vec3) {
+        self.velocity += impulse / self.mass;
+    }
+}
+
+/// Integrates the velocity of every rigid body, and resolves any penetrations with static models by pushing the body
+/// out along the minimum translation vector.
+pub fn physics_step(world: &mut World, renderer: &RenderContext, bvh: &mut BVH<Entity>, dt: f32) {
+    let bodies: Vec<Entity> = world
+        .query::<(&RigidBody, &ModelComponent)>()
+        .iter()
+        .map(|(entity, _)| entity)
+        .collect();
+
+    for entity in bodies {
+        let mut body = *world.get::<&RigidBody>(entity).unwrap();
+        let (start, aabb) = {
+            let model = world.get::<&ModelComponent>(entity).unwrap();
+            (model.get_position(), renderer.get_model_aabb(&model))
+        };
+
+        body.velocity += body.acceleration * dt;
+        let mut displacement = body.velocity * dt;
+        if displacement.magnitude() > aabb.extents().min() * 0.5 {
+            displacement = sweep_static(world, renderer, bvh, entity, &aabb, displacement);
+        }
+        let mut aabb = aabb.translate(displacement);
+        body.grounded = false;
+
+        let others: Vec<Entity> = bvh.iter_aabb(&aabb).filter(|o| *o != entity).collect();
+        for other in others {
+            if world.get::<&RigidBody>(other).is_ok() {
+                // Only resolve against static models
+                continue;
+            }
+            let Ok(other_model) = world.get::<&ModelComponent>(other) else {
+                continue;
+            };
+            let push = aabb.penetration(&renderer.get_model_aabb(&other_model));
+            if push == nalgebra_glm::Vec3::zeros() {
+                continue;
+            }
+
+            aabb = aabb.translate(push);
+            displacement += push;
+
+            // Cancel out the velocity going into the surface, so that the body slides along it
+            for axis in 0..3 {
+                if push[axis] != 0.0 && push[axis].signum() != body.velocity[axis].signum() {
+                    body.velocity[axis] = 0.0;
+                }
+            }
+            if push.z > 0.0 {
+                body.grounded = true;
+            }
+        }
+
+        world
+            .get::<&mut ModelComponent>(entity)
+            .unwrap()
+            .set_position(start + displacement);
+        *world.get::<&mut RigidBody>(entity).unwrap() = body;
+        bvh.move_obj(body.bvh_node, &aabb, &displacement);
+    }
+}
+
+/// Stops a fast-moving body where it first touches a static model, so that it can't pass through thin colliders in a
+/// single step
+fn sweep_static(
+    world: &World,
+    renderer: &RenderContext,
+    bvh: &BVH<Entity>,
+    entity: Entity,
+    aabb: &AABB,
+    displacement: nalgebra_glm::Vec3,
+) -> nalgebra_glm::Vec3 {
+    // How far past the point of contact to move the body. This leaves a small penetration, which is resolved along the
+    // face the body hit, and cancels its velocity into that face.
+    const SKIN: f32 = 0.001;
+
+    let swept = aabb.union(aabb.translate(displacement));
+    let first_hit = bvh
+        .iter_aabb(&swept)
+        .filter(|other| *other != entity && world.get::<&RigidBody>(*other).is_err())
+        .filter_map(|other| {
+            let other_model = world.get::<&ModelComponent>(other).ok()?;
+            aabb.sweep(displacement, &renderer.get_model_aabb(&other_model))
+        })
+        // Models the body already touches are handled by resolving penetrations
+        .filter(|t| *t > 0.0)
+        .min_by(f32::total_cmp);
+
+    match first_hit {
+        Some(t) => displacement * t + displacement.normalize() * SKIN,
+        None => displacement,
+    }
+}
+
+/// Keeps every rigid body from sinking below the terrain. Bodies below the ground are lifted onto it, lose the part of
+/// their velocity going into the slope, and are marked as grounded. Call this after `physics_step`.
+pub fn keep_on_terrain(
+    world: &mut World,
+    renderer: &RenderContext,
+    bvh: &mut BVH<Entity>,
+    terrain: &dyn HeightMap,
+) {
+    for (_entity, (body, model)) in world.query_mut::<(&mut RigidBody, &mut ModelComponent)>() {
+        let aabb = renderer.get_model_aabb(model);
+        let center = aabb.center();
+        let extents = aabb.extents();
+        let radius = extents.x.max(extents.y) / 2.0;
+        let (ground_height, ground_normal) =
+            terrain.ground_contact(nalgebra_glm::vec2(center.x, center.y), radius);
+
+        let depth = ground_height - aabb.min.z;
+        if depth < 0.0 {
+            continue;
+        }
+        let lift = nalgebra_glm::vec3(0.0, 0.0, depth);
+        model.set_position(model.get_position() + lift);
+        bvh.move_obj(body.bvh_node, &aabb.translate(lift), &lift);
+
+        let into_ground = body.velocity.dot(&ground_normal);
+        if into_ground < 0.0 {
+            body.velocity -= ground_normal * into_ground;
+        }
+        body.grounded = true;
+    }
+}
+
+/// Finds the first model struck by a ray. This is AABB-only for now: the ray is tested against each model's AABB
+/// rather than it's mesh, so the point and normal are those of the AABB face that was struck.
+pub fn raycast_world(
+    world: &World,
+    renderer: &RenderContext,
+    bvh: &BVH<Entity>,
+    ray: &Ray,
+) -> Option<RayHit> {
+    let mut closest: Option<RayHit> = None;
+    for entity in bvh.iter_ray(ray) {
+        let Ok(model) = world.get::<&ModelComponent>(entity) else {
+            continue;
+        };
+        let Some((distance, normal)) = renderer.get_model_aabb(&model).ray_entry(ray) else {
+            continue;
+        };
+        if closest.is_none_or(|hit| distance < hit.distance) {
+            closest = Some(RayHit {
+                entity,
+                point: ray.at(distance),
+                normal,
+                distance,
+            });
+        }
+    }
+    closest
+}
@@ -1,5 +1,18 @@
 //! This module defines a normal-distance plane.
 
+use super::ray::Ray;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Which side of a plane a point is on
+pub enum Side {
+    /// The side the normal points toward
+    Front,
+    /// The side the normal points away from
+    Back,
+    /// On the plane itself, within `Plane::EPSILON`
+    On,
+}
+
 #[derive(Debug, Copy, Clone)]
 /// A plane data structure
 pub struct Plane {
@@ -8,6 +21,9 @@ pub struct Plane {
 }
 
 impl Plane {
+    /// How far a point can be from a plane and still be considered on it
+    pub const EPSILON: f32 = 1e-5;
+
     /// Create a new plane from a normal and distance from the origin along that normal
     pub fn new(normal: nalgebra_glm::Vec3, dist: f32) -> Self {
         Self { normal, dist }
@@ -31,4 +47,41 @@ impl Plane {
     pub fn dist(&self) -> f32 {
         self.dist
     }
+
+    /// The distance from the plane to a point, positive in front of the plane and negative behind it
+    pub fn signed_distance(&self, p: nalgebra_glm::Vec3) -> f32 {
+        self.normal.dot(&p) + self.dist
+    }
+
+    /// Determines which side of the plane a point is on
+    pub fn classify_point(&self, p: nalgebra_glm::Vec3) -> Side {
+        let distance = self.signed_distance(p);
+        if distance > Self::EPSILON {
+            Side::Front
+        } else if distance < -Self::EPSILON {
+            Side::Back
+        } else {
+            Side::On
+        }
+    }
+
+    /// Finds the line where two planes meet, as a ray pointing along the cross product of their normals. Returns `None`
+    /// if the planes are parallel.
+    pub fn intersect_plane(&self, other: &Plane) -> Option<Ray> {
+        let dir = self.normal.cross(&other.normal);
+        let n1_n1 = self.normal.dot(&self.normal);
+        let n2_n2 = other.normal.dot(&other.normal);
+        let n1_n2 = self.normal.dot(&other.normal);
+        let det = n1_n1 * n2_n2 - n1_n2 * n1_n2;
+        if det.abs() < Self::EPSILON {
+            return None;
+        }
+
+        // The point on the line closest to the origin is a combination of both normals; solve the 2x2 system for it
+        let (h1, h2) = (-self.dist, -other.dist);
+        let c1 = (h1 * n2_n2 - h2 * n1_n2) / det;
+        let c2 = (h2 * n1_n1 - h1 * n1_n2) / det;
+        let origin = self.normal * c1 + other.normal * c2;
+        Some(Ray::new(origin, dir.normalize()))
+    }
 }
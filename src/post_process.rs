@@ -0,0 +1,286 @@
+//! This module contains a post-processing chain, which renders the scene offscreen before compositing it to the screen.
+//!
+//! The scene is rendered into an HDR color target, so that colors brighter than 1.0 survive. If bloom is enabled, pixels
+//! brighter than a threshold are extracted, blurred with a separable Gaussian blur that ping-pongs between two targets,
+//! and added back on top of the scene. Finally, the HDR colors can be tone mapped down to what the screen can show.
+
+use super::{
+    opengl::{create_program, Fbo, FboScope, Texture},
+    render_core::{ProgramId, RenderContext, FULLSCREEN_VERT},
+};
+
+const BRIGHT_FRAG: &str = r#"
+#version 330 core
+in vec2 v_uv;
+out vec4 out_color;
+
+uniform sampler2D u_scene;
+uniform float u_threshold;
+
+void main() {
+    vec3 color = texture(u_scene, v_uv).rgb;
+    float brightness = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    out_color = vec4(brightness > u_threshold ? color : vec3(0.0), 1.0);
+}
+"#;
+
+const BLUR_FRAG: &str = r#"
+#version 330 core
+in vec2 v_uv;
+out vec4 out_color;
+
+uniform sampler2D u_image;
+uniform vec2 u_direction;
+
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+    vec2 texel = u_direction / vec2(textureSize(u_image, 0));
+    vec3 result = texture(u_image, v_uv).rgb * weights[0];
+    for (int i = 1; i < 5; i++) {
+        result += texture(u_image, v_uv + texel * float(i)).rgb * weights[i];
+        result += texture(u_image, v_uv - texel * float(i)).rgb * weights[i];
+    }
+    out_color = vec4(result, 1.0);
+}
+"#;
+
+const COMPOSITE_FRAG: &str = r#"
+#version 330 core
+in vec2 v_uv;
+out vec4 out_color;
+
+uniform sampler2D u_scene;
+uniform sampler2D u_bloom;
+uniform float u_intensity;
+// 0 is no tone mapping, 1 is Reinhard, 2 is ACES
+uniform int u_tone_mapping;
+
+vec3 aces(vec3 x) {
+    // Krzysztof Narkowicz's fit of the ACES filmic curve
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec3 color = texture(u_scene, v_uv).rgb;
+    // The bloom target is never written to while bloom is disabled, so don't sample it
+    if (u_intensity > 0.0) {
+        color += texture(u_bloom, v_uv).rgb * u_intensity;
+    }
+    if (u_tone_mapping == 1) {
+        color = color / (color + vec3(1.0));
+    } else if (u_tone_mapping == 2) {
+        color = aces(color);
+    }
+    out_color = vec4(color, 1.0);
+}
+"#;
+
+/// How many times the bright pixels are blurred horizontally and then vertically
+const BLUR_PASSES: usize = 5;
+
+/// How HDR colors are mapped to the range the screen can show when they're composited
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToneMapping {
+    /// Colors brighter than 1.0 are clipped
+    None,
+    /// Reinhard's operator, which is simple and never clips but can look washed out
+    Reinhard,
+    /// An approximation of the ACES filmic curve, which keeps more contrast
+    Aces,
+}
+
+/// Offscreen render targets and settings for post-processing
+pub struct PostProcess {
+    size: (i32, i32),
+
+    scene_fbo: Fbo,
+    scene_color: Texture,
+    scene_depth: Texture,
+
+    ping_pong_fbos: [Fbo; 2],
+    ping_pong_colors: [Texture; 2],
+
+    bright_program: ProgramId,
+    blur_program: ProgramId,
+    composite_program: ProgramId,
+
+    bloom_enabled: bool,
+    bloom_threshold: f32,
+    bloom_intensity: f32,
+
+    tone_mapping: ToneMapping,
+}
+
+impl PostProcess {
+    /// Creates the render targets and adds the post-processing programs to the renderer
+    pub fn new(renderer: &RenderContext, width: i32, height: i32) -> Result<Self, String> {
+        let bright_program = Self::get_or_add_program(renderer, "post-bright", BRIGHT_FRAG)?;
+        let blur_program = Self::get_or_add_program(renderer, "post-blur", BLUR_FRAG)?;
+        let composite_program =
+            Self::get_or_add_program(renderer, "post-composite", COMPOSITE_FRAG)?;
+
+        let mut retval = Self {
+            size: (0, 0),
+            scene_fbo: Fbo::new(),
+            scene_color: Texture::new(),
+            scene_depth: Texture::new(),
+            ping_pong_fbos: [Fbo::new(), Fbo::new()],
+            ping_pong_colors: [Texture::new(), Texture::new()],
+            bright_program,
+            blur_program,
+            composite_program,
+            bloom_enabled: false,
+            bloom_threshold: 1.0,
+            bloom_intensity: 1.0,
+            tone_mapping: ToneMapping::None,
+        };
+        retval.resize(width, height)?;
+        Ok(retval)
+    }
+
+    /// Turns bloom on or off. Pixels brighter than `threshold` bleed into their surroundings, scaled by `intensity`.
+    pub fn set_bloom(&mut self, enabled: bool, threshold: f32, intensity: f32) {
+        self.bloom_enabled = enabled;
+        self.bloom_threshold = threshold;
+        self.bloom_intensity = intensity;
+    }
+
+    /// Sets how the scene's HDR colors are mapped to the screen
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.tone_mapping = tone_mapping;
+    }
+
+    /// Reallocates the render targets for a new screen size
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), String> {
+        if self.size == (width, height) {
+            return Ok(());
+        }
+        self.size = (width, height);
+        let _scope = FboScope::save();
+
+        self.scene_color.load_hdr_color_buffer(width, height);
+        self.scene_depth.load_depth_buffer(width, height);
+        self.scene_fbo.attach_color(&self.scene_color);
+        self.scene_fbo.attach_depth(&self.scene_depth);
+        let result = self.scene_fbo.check_complete();
+
+        let result = result.and_then(|_| {
+            for (fbo, color) in self.ping_pong_fbos.iter().zip(self.ping_pong_colors.iter()) {
+                color.load_hdr_color_buffer(width, height);
+                fbo.attach_color(color);
+                fbo.check_complete()?;
+            }
+            Ok(())
+        });
+        result
+    }
+
+    fn get_or_add_program(
+        renderer: &RenderContext,
+        name: &'static str,
+        frag_data: &'static str,
+    ) -> Result<ProgramId, String> {
+        if let Some(program_id) = renderer.get_program_id_from_name(name) {
+            return Ok(program_id);
+        }
+        let program = create_program(FULLSCREEN_VERT, frag_data)?;
+        Ok(renderer.add_program(program, Some(name)))
+    }
+}
+
+impl RenderContext {
+    /// Renders everything drawn in `f` into the post-processing chain, then composites the result onto the framebuffer
+    /// that was bound before. `f` can render into other FBOs, like shadow maps or render targets, since those passes
+    /// restore the scene's binding when they finish.
+    pub fn render_post_processed(
+        &self,
+        post: &mut PostProcess,
+        f: impl FnOnce(),
+    ) -> Result<(), String> {
+        post.resize(self.int_screen_resolution.x, self.int_screen_resolution.y)?;
+        let (width, height) = post.size;
+
+        let scene_scope = post.scene_fbo.bind_scoped();
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+        }
+        f();
+        self.flush_2d();
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::BLEND);
+        }
+
+        // Which of the ping-pong targets holds the finished blur
+        let mut bloom_target = 0;
+        if post.bloom_enabled {
+            post.ping_pong_fbos[0].bind();
+            self.set_program_from_id(post.bright_program);
+            post.scene_color.activate(gl::TEXTURE0);
+            post.scene_color
+                .associate_uniform(self.get_current_program_id(), 0, "u_scene");
+            let u_threshold = self.get_program_uniform("u_threshold")?;
+            unsafe {
+                gl::Uniform1f(u_threshold.id, post.bloom_threshold);
+            }
+            self.draw_fullscreen();
+
+            self.set_program_from_id(post.blur_program);
+            let u_direction = self.get_program_uniform("u_direction")?;
+            for pass in 0..BLUR_PASSES * 2 {
+                let (src, dst) = (pass % 2, (pass + 1) % 2);
+                post.ping_pong_fbos[dst].bind();
+                post.ping_pong_colors[src].activate(gl::TEXTURE0);
+                post.ping_pong_colors[src].associate_uniform(
+                    self.get_current_program_id(),
+                    0,
+                    "u_image",
+                );
+                unsafe {
+                    if pass % 2 == 0 {
+                        gl::Uniform2f(u_direction.id, 1.0, 0.0);
+                    } else {
+                        gl::Uniform2f(u_direction.id, 0.0, 1.0);
+                    }
+                }
+                self.draw_fullscreen();
+                bloom_target = dst;
+            }
+        }
+
+        // Back to the framebuffer and viewport the composite draws into
+        drop(scene_scope);
+
+        self.set_program_from_id(post.composite_program);
+        post.scene_color.activate(gl::TEXTURE0);
+        post.scene_color
+            .associate_uniform(self.get_current_program_id(), 0, "u_scene");
+        post.ping_pong_colors[bloom_target].activate(gl::TEXTURE1);
+        post.ping_pong_colors[bloom_target].associate_uniform(
+            self.get_current_program_id(),
+            1,
+            "u_bloom",
+        );
+        let u_intensity = self.get_program_uniform("u_intensity")?;
+        let u_tone_mapping = self.get_program_uniform("u_tone_mapping")?;
+        let intensity = if post.bloom_enabled {
+            post.bloom_intensity
+        } else {
+            0.0
+        };
+        unsafe {
+            gl::Uniform1f(u_intensity.id, intensity);
+            gl::Uniform1i(u_tone_mapping.id, post.tone_mapping as i32);
+        }
+        self.draw_fullscreen();
+
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,20 @@
 //! This module defines a ray
 
+#[derive(Debug, Copy, Clone)]
 /// A ray data structure
 pub struct Ray {
     pub origin: nalgebra_glm::Vec3,
     pub dir: nalgebra_glm::Vec3,
 }
+
+impl Ray {
+    /// Create a new ray from an origin and direction
+    pub fn new(origin: nalgebra_glm::Vec3, dir: nalgebra_glm::Vec3) -> Self {
+        Self { origin, dir }
+    }
+
+    /// Finds the point a distance `t` along the ray, in units of the ray's direction
+    pub fn at(&self, t: f32) -> nalgebra_glm::Vec3 {
+        self.origin + self.dir * t
+    }
+}
@@ -1,6 +1,6 @@
 //! This module defines a rectangle
 
-#[derive(Default, Copy, Clone)]
+#[derive(Default, Debug, Copy, Clone)]
 /// A rectangle data structure
 pub struct Rectangle {
     pub pos: nalgebra_glm::Vec2,
@@ -15,10 +15,49 @@ impl Rectangle {
         }
     }
 
+    /// Create a rectangle of a given size, centered on a point
+    pub fn from_center(center: nalgebra_glm::Vec2, size: nalgebra_glm::Vec2) -> Self {
+        Self {
+            pos: center - size * 0.5,
+            size,
+        }
+    }
+
     pub fn contains_point(&self, p: &nalgebra_glm::Vec2) -> bool {
         self.pos.x <= p.x
             && self.pos.y <= p.y
             && self.pos.x + self.size.x >= p.x
             && self.pos.y + self.size.y >= p.y
     }
+
+    /// Determines whether two rectangles overlap
+    pub fn intersects(&self, other: &Rectangle) -> bool {
+        self.pos.x < other.pos.x + other.size.x
+            && other.pos.x < self.pos.x + self.size.x
+            && self.pos.y < other.pos.y + other.size.y
+            && other.pos.y < self.pos.y + self.size.y
+    }
+
+    /// Finds the region where two rectangles overlap, if they overlap at all
+    pub fn intersection(&self, other: &Rectangle) -> Option<Rectangle> {
+        if !self.intersects(other) {
+            return None;
+        }
+        let min = nalgebra_glm::max2(&self.pos, &other.pos);
+        let max = nalgebra_glm::min2(&(self.pos + self.size), &(other.pos + other.size));
+        Some(Rectangle {
+            pos: min,
+            size: max - min,
+        })
+    }
+
+    /// Union two rectangles to form a single rectangle that contains both
+    pub fn union(&self, other: &Rectangle) -> Rectangle {
+        let min = nalgebra_glm::min2(&self.pos, &other.pos);
+        let max = nalgebra_glm::max2(&(self.pos + self.size), &(other.pos + other.size));
+        Rectangle {
+            pos: min,
+            size: max - min,
+        }
+    }
 }
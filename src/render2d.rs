@@ -1,10 +1,89 @@
 use std::borrow::Borrow;
 
 use super::{
+    opengl::{create_program, Buffer, Vao},
     rectangle::Rectangle,
-    render_core::{RenderContext, TextureId},
+    render_core::{OpaqueId, ProgramId, RenderContext, TextureId},
 };
 
+const SPRITE_BATCH_VERT: &str = r#"
+#version 330 core
+layout (location = 0) in vec2 position;
+layout (location = 1) in vec2 uv;
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_proj_matrix;
+
+out vec2 v_uv;
+
+void main() {
+    v_uv = uv;
+    gl_Position = u_proj_matrix * u_view_matrix * vec4(position, -3.0, 1.0);
+}
+"#;
+
+const SPRITE_BATCH_FRAG: &str = r#"
+#version 330 core
+in vec2 v_uv;
+out vec4 out_color;
+
+uniform sampler2D texture0;
+
+void main() {
+    out_color = texture(texture0, v_uv);
+}
+"#;
+
+/// Textured quads waiting to be drawn. Quads that share a texture are drawn together in one draw call.
+pub(crate) struct SpriteBatch {
+    texture: Option<TextureId>,
+    positions: Vec<f32>,
+    uvs: Vec<f32>,
+    indices: Vec<u32>,
+
+    vao: Vao,
+    positions_buffer: Buffer<f32>,
+    uvs_buffer: Buffer<f32>,
+    indices_buffer: Buffer<u32>,
+}
+
+impl SpriteBatch {
+    pub(crate) fn new() -> Self {
+        Self {
+            texture: None,
+            positions: vec![],
+            uvs: vec![],
+            indices: vec![],
+            vao: Vao::gen_with_components(2),
+            positions_buffer: Buffer::gen(gl::ARRAY_BUFFER),
+            uvs_buffer: Buffer::gen(gl::ARRAY_BUFFER),
+            indices_buffer: Buffer::gen(gl::ELEMENT_ARRAY_BUFFER),
+        }
+    }
+
+    fn push_quad(&mut self, dest: Rectangle, uv: Rectangle) {
+        let first = (self.positions.len() / 2) as u32;
+        let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        for (x, y) in corners {
+            self.positions.push(dest.pos.x + x * dest.size.x);
+            self.positions.push(dest.pos.y + y * dest.size.y);
+            self.uvs.push(uv.pos.x + x * uv.size.x);
+            self.uvs.push(uv.pos.y + y * uv.size.y);
+        }
+        // The y axis points down the screen, so these wind counter-clockwise on screen
+        for i in [0, 2, 1, 0, 3, 2] {
+            self.indices.push(first + i);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.texture = None;
+        self.positions.clear();
+        self.uvs.clear();
+        self.indices.clear();
+    }
+}
+
 pub struct NineSlice {
     pub texture: TextureId,
     pub border: f32,
@@ -159,8 +238,114 @@ impl RenderContext {
         font.draw(pos, text, self);
     }
 
-    // TODO: Rename `copy_texture` or something, implement `fill_rect` with 2d-color.frag shader
+    /// The size of the box that `draw_text` would fill with the current font
+    pub fn measure_text(&self, text: &str) -> nalgebra_glm::Vec2 {
+        let font = self.get_font_from_id(self.font.borrow().unwrap()).unwrap();
+        font.measure(text)
+    }
+
+    // TODO: Rename `copy_texture` or something
+    /// Queues a region of a texture to be drawn to a rectangle on the screen, in pixels. Queued quads are drawn when
+    /// `flush_2d` is called, or when a quad with a different texture is queued.
     pub fn copy_texture(&self, dest: Rectangle, texture_id: TextureId, texture_dest: Rectangle) {
+        let batch_texture = self.sprite_batch.borrow().texture;
+        if batch_texture.is_some_and(|texture| texture.as_usize() != texture_id.as_usize()) {
+            self.flush_2d();
+        }
+
+        let (texture_width, texture_height) = self
+            .get_texture_from_id(texture_id)
+            .unwrap()
+            .get_dimensions()
+            .unwrap();
+        let uv = Rectangle::new(
+            texture_dest.pos.x / texture_width as f32,
+            texture_dest.pos.y / texture_height as f32,
+            texture_dest.size.x / texture_width as f32,
+            texture_dest.size.y / texture_height as f32,
+        );
+
+        let mut batch = self.sprite_batch.borrow_mut();
+        batch.texture = Some(texture_id);
+        batch.push_quad(dest, uv);
+    }
+
+    /// Draws all queued 2D quads
+    pub fn flush_2d(&self) {
+        let mut batch = self.sprite_batch.borrow_mut();
+        let Some(texture_id) = batch.texture else {
+            return;
+        };
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST); // Disable depth test for 2D rendering
+            gl::Enable(gl::CULL_FACE);
+            gl::CullFace(gl::BACK);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+
+        self.set_program_from_id(self.sprite_batch_program());
+        let (view_matrix, proj_matrix) = self.camera_2d.borrow().view_proj_matrices();
+        let u_view_matrix = self.get_program_uniform("u_view_matrix").unwrap();
+        let u_proj_matrix = self.get_program_uniform("u_proj_matrix").unwrap();
+        unsafe {
+            gl::UniformMatrix4fv(
+                u_view_matrix.id,
+                1,
+                gl::FALSE,
+                &view_matrix.columns(0, 4)[0],
+            );
+            gl::UniformMatrix4fv(
+                u_proj_matrix.id,
+                1,
+                gl::FALSE,
+                &proj_matrix.columns(0, 4)[0],
+            );
+        }
+
+        let texture = self.get_texture_from_id(texture_id).unwrap();
+        texture.activate(gl::TEXTURE0);
+        texture.associate_uniform(self.get_current_program_id(), 0, "texture0");
+
+        unsafe {
+            gl::BindVertexArray(batch.vao.id);
+        }
+        batch.positions_buffer.set_data(&batch.positions);
+        batch.vao.enable(0);
+        batch.uvs_buffer.set_data(&batch.uvs);
+        batch.vao.enable(1);
+        batch.indices_buffer.set_data(&batch.indices);
+        unsafe {
+            gl::DrawElements(
+                gl::TRIANGLES,
+                batch.indices.len() as i32,
+                gl::UNSIGNED_INT,
+                std::ptr::null(),
+            );
+        }
+        batch.positions_buffer.unbind();
+        batch.indices_buffer.unbind();
+
+        batch.clear();
+    }
+
+    fn sprite_batch_program(&self) -> ProgramId {
+        if let Some(program_id) = self.get_program_id_from_name("2d-batch") {
+            return program_id;
+        }
+        let program = create_program(SPRITE_BATCH_VERT, SPRITE_BATCH_FRAG).unwrap();
+        self.add_program(program, Some("2d-batch"))
+    }
+
+    /// Draws a region of a texture to a rectangle on the screen right away, without batching
+    pub fn copy_texture_immediate(
+        &self,
+        dest: Rectangle,
+        texture_id: TextureId,
+        texture_dest: Rectangle,
+    ) {
+        self.flush_2d();
         let res = self.int_screen_resolution.borrow();
         unsafe {
             gl::Viewport(0, 0, res.x, res.y);
@@ -173,18 +358,8 @@ impl RenderContext {
 
         self.set_program_from_id(self.get_program_id_from_name("2d").unwrap());
 
-        let (view_matrix, proj_matrix) = self.camera_2d.view_proj_matrices();
-        let model_matrix: nalgebra_glm::Mat4 = nalgebra_glm::scale(
-            &nalgebra_glm::translate(
-                &nalgebra_glm::one(),
-                &nalgebra_glm::vec3(
-                    1.0 - 2.0 * dest.pos.x / res.x as f32 - dest.size.x / res.x as f32,
-                    1.0 - 2.0 * dest.pos.y / res.y as f32 - dest.size.y / res.y as f32,
-                    3.0,
-                ),
-            ),
-            &nalgebra_glm::vec3(dest.size.x / res.x as f32, dest.size.y / res.y as f32, 0.1),
-        );
+        let (view_matrix, proj_matrix) = self.camera_2d.borrow().view_proj_matrices();
+        let model_matrix = quad_model_matrix(dest);
 
         let texture = self.get_texture_from_id(texture_id).unwrap();
         let (texture_width, texture_height) = texture.get_dimensions().unwrap();
@@ -214,6 +389,9 @@ impl RenderContext {
     }
 
     pub fn fill_rect(&self, dest: Rectangle) {
+        // Keep things drawn in order
+        self.flush_2d();
+
         let res = self.int_screen_resolution.borrow();
         unsafe {
             gl::Viewport(0, 0, res.x, res.y);
@@ -237,18 +415,8 @@ impl RenderContext {
             );
         }
 
-        let (view_matrix, proj_matrix) = self.camera_2d.view_proj_matrices();
-        let model_matrix: nalgebra_glm::Mat4 = nalgebra_glm::scale(
-            &nalgebra_glm::translate(
-                &nalgebra_glm::one(),
-                &nalgebra_glm::vec3(
-                    1.0 - 2.0 * dest.pos.x / res.x as f32 - dest.size.x / res.x as f32,
-                    1.0 - 2.0 * dest.pos.y / res.y as f32 - dest.size.y / res.y as f32,
-                    3.0,
-                ),
-            ),
-            &nalgebra_glm::vec3(dest.size.x / res.x as f32, dest.size.y / res.y as f32, 0.1),
-        );
+        let (view_matrix, proj_matrix) = self.camera_2d.borrow().view_proj_matrices();
+        let model_matrix = quad_model_matrix(dest);
 
         let quad_mesh = self
             .get_mesh_from_id(self.get_mesh_id_from_name("quad-xy").unwrap())
@@ -256,3 +424,16 @@ impl RenderContext {
         self.draw(quad_mesh.borrow(), model_matrix, view_matrix, proj_matrix);
     }
 }
+
+/// Places the `quad-xy` mesh, which spans -1 to 1, over a rectangle in pixels. The quad is turned half way around, so
+/// that its texture coordinates run from the top-left of the rectangle.
+fn quad_model_matrix(dest: Rectangle) -> nalgebra_glm::Mat4 {
+    let center = dest.pos + dest.size / 2.0;
+    nalgebra_glm::scale(
+        &nalgebra_glm::translate(
+            &nalgebra_glm::one(),
+            &nalgebra_glm::vec3(center.x, center.y, -3.0),
+        ),
+        &nalgebra_glm::vec3(-dest.size.x / 2.0, -dest.size.y / 2.0, 0.1),
+    )
+}
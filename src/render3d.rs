@@ -3,23 +3,310 @@ use std::borrow::Borrow;
 use crate::render_core::LinePathComponent;
 
 use super::{
+    aabb::AABB,
     bvh::BVH,
+    camera::{Camera, WORLD_UP},
+    frustum::Frustum,
     opengl::*,
-    render_core::{ModelComponent, RenderContext},
+    render_core::{
+        CullingStats, Mesh, ModelComponent, OpaqueId, ProgramId, RenderContext, TextureId,
+    },
     shadow_map::DirectionalLightSource,
+    transform::model_matrix_of,
 };
 
 use hecs::{Entity, World};
 
+const WATER_VERT: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 position;
+
+uniform mat4 u_model_matrix;
+uniform mat4 u_view_matrix;
+uniform mat4 u_proj_matrix;
+
+out vec3 v_world;
+
+void main() {
+    v_world = (u_model_matrix * vec4(position, 1.0)).xyz;
+    gl_Position = u_proj_matrix * u_view_matrix * vec4(v_world, 1.0);
+}
+"#;
+
+const WATER_FRAG: &str = r#"
+#version 330 core
+in vec3 v_world;
+out vec4 out_color;
+
+uniform float u_time;
+uniform vec3 u_camera_pos;
+uniform vec4 u_color;
+
+void main() {
+    // A couple of scrolling waves, summed into a surface normal
+    vec2 p = v_world.xy;
+    float dx = 0.08 * cos(p.x * 0.7 + u_time * 1.3) + 0.05 * cos((p.x + p.y) * 1.3 + u_time * 0.9);
+    float dy = 0.08 * cos(p.y * 0.6 - u_time * 1.1) + 0.05 * cos((p.x + p.y) * 1.3 + u_time * 0.9);
+    vec3 normal = normalize(vec3(-dx, -dy, 1.0));
+
+    vec3 sun_dir = normalize(vec3(0.3, 0.2, 1.0));
+    vec3 view_dir = normalize(u_camera_pos - v_world);
+    float diffuse = 0.6 + 0.4 * max(dot(normal, sun_dir), 0.0);
+    float specular = pow(max(dot(reflect(-sun_dir, normal), view_dir), 0.0), 64.0);
+    // Water is more see-through when looked at from straight above
+    float fresnel = pow(1.0 - max(dot(normal, view_dir), 0.0), 3.0);
+
+    vec3 color = u_color.rgb * diffuse + vec3(specular);
+    out_color = vec4(color, mix(u_color.a, 1.0, fresnel));
+}
+"#;
+
+/// Builds the source of a variant of the built-in model shaders, which turns on features with `#define`s. Models are
+/// lit by the directional light in `u_sun_dir`, which points toward the light, and shadowed by its shadow map. The view
+/// and projection matrices come from the camera block, see `CAMERA_BLOCK_BINDING`.
+macro_rules! model_vert {
+    ($defines:literal) => {
+        concat!(
+            "#version 330 core\n",
+            $defines,
+            r#"
+layout (location = 0) in vec3 a_position;
+layout (location = 1) in vec3 a_normal;
+layout (location = 2) in vec3 a_uv;
+#ifdef VERTEX_COLOR
+layout (location = 3) in vec4 a_color;
+#endif
+#ifdef TERRAIN
+layout (location = 4) in vec4 a_weights;
+#endif
+#ifdef NORMAL_MAP
+layout (location = 5) in vec4 a_tangent;
+#endif
+
+layout (std140) uniform Camera {
+    mat4 u_view_matrix;
+    mat4 u_proj_matrix;
+    vec4 u_camera_pos;
+};
+uniform mat4 u_model_matrix;
+uniform mat3 u_normal_matrix;
+uniform mat4 light_mvp;
+
+out vec3 v_normal;
+out vec2 v_uv;
+out vec4 v_light_space;
+#ifdef VERTEX_COLOR
+out vec4 v_color;
+#endif
+#ifdef TERRAIN
+out vec4 v_weights;
+#endif
+#ifdef NORMAL_MAP
+out vec4 v_tangent;
+#endif
+
+void main() {
+    vec4 world = u_model_matrix * vec4(a_position, 1.0);
+    v_normal = normalize(u_normal_matrix * a_normal);
+#ifdef NORMAL_MAP
+    v_tangent = vec4(normalize(mat3(u_model_matrix) * a_tangent.xyz), a_tangent.w);
+#endif
+    v_uv = a_uv.xy;
+#ifdef VERTEX_COLOR
+    v_color = a_color;
+#endif
+#ifdef TERRAIN
+    v_weights = a_weights;
+#endif
+    v_light_space = light_mvp * world;
+    gl_Position = u_proj_matrix * u_view_matrix * world;
+}
+"#
+        )
+    };
+}
+
+/// The fragment shader to go with `model_vert`, with the same `#define`s
+macro_rules! model_frag {
+    ($defines:literal) => {
+        concat!(
+            "#version 330 core\n",
+            $defines,
+            r#"
+in vec3 v_normal;
+in vec2 v_uv;
+in vec4 v_light_space;
+#ifdef VERTEX_COLOR
+in vec4 v_color;
+#endif
+#ifdef TERRAIN
+in vec4 v_weights;
+#endif
+#ifdef NORMAL_MAP
+in vec4 v_tangent;
+#endif
+out vec4 out_color;
+
+uniform sampler2D texture0;
+uniform sampler2D shadow_map;
+uniform vec3 u_sun_dir;
+#ifdef CUTOUT
+uniform float u_alpha_cutout;
+#endif
+#ifdef NORMAL_MAP
+uniform sampler2D normal_map;
+#endif
+
+const float AMBIENT = 0.35;
+#ifdef TERRAIN
+// Where grass, sand, and cliff are across the terrain texture atlas, like the atlas offsets in `chunked_map`
+const vec3 MATERIAL_U = vec3(0.0, 3.0 / 9.0, 5.0 / 9.0);
+#endif
+
+float shadow(vec3 normal) {
+    vec3 p = v_light_space.xyz / v_light_space.w * 0.5 + 0.5;
+    if (p.z > 1.0) {
+        return 0.0;
+    }
+    float bias = max(0.005 * (1.0 - dot(normal, u_sun_dir)), 0.0005);
+    return p.z - bias > texture(shadow_map, p.xy).r ? 1.0 : 0.0;
+}
+
+void main() {
+#ifdef TERRAIN
+    // Blend the land materials by their weights. Water is left white, for the vertex color to tint.
+    vec4 albedo = texture(texture0, vec2(MATERIAL_U.x, v_uv.y)) * v_weights.x
+        + texture(texture0, vec2(MATERIAL_U.y, v_uv.y)) * v_weights.y
+        + texture(texture0, vec2(MATERIAL_U.z, v_uv.y)) * v_weights.z
+        + vec4(v_weights.w);
+#else
+    vec4 albedo = texture(texture0, v_uv);
+#endif
+#ifdef VERTEX_COLOR
+    albedo *= v_color;
+#endif
+#ifdef CUTOUT
+    if (albedo.a < u_alpha_cutout) {
+        discard;
+    }
+#endif
+    vec3 normal = normalize(v_normal);
+#ifdef NORMAL_MAP
+    // Re-orthogonalize the interpolated tangent, then move the sampled normal from tangent space to world space
+    vec3 tangent = normalize(v_tangent.xyz - normal * dot(normal, v_tangent.xyz));
+    vec3 bitangent = cross(normal, tangent) * v_tangent.w;
+    vec3 sampled = texture(normal_map, v_uv).xyz * 2.0 - 1.0;
+    normal = normalize(mat3(tangent, bitangent, normal) * sampled);
+#endif
+    float diffuse = max(dot(normal, u_sun_dir), 0.0) * (1.0 - shadow(normal));
+    out_color = vec4(albedo.rgb * (AMBIENT + (1.0 - AMBIENT) * diffuse), albedo.a);
+}
+"#
+        )
+    };
+}
+
+const CUTOUT_VERT: &str = model_vert!("#define CUTOUT\n");
+const CUTOUT_FRAG: &str = model_frag!("#define CUTOUT\n");
+const NORMAL_MAP_VERT: &str = model_vert!("#define NORMAL_MAP\n");
+const NORMAL_MAP_FRAG: &str = model_frag!("#define NORMAL_MAP\n");
+const VERTEX_COLOR_VERT: &str = model_vert!("#define VERTEX_COLOR\n");
+const VERTEX_COLOR_FRAG: &str = model_frag!("#define VERTEX_COLOR\n");
+// Terrain meshes also have vertex colors, for biome tints and rivers
+const TERRAIN_VERT: &str = model_vert!("#define VERTEX_COLOR\n#define TERRAIN\n");
+const TERRAIN_FRAG: &str = model_frag!("#define VERTEX_COLOR\n#define TERRAIN\n");
+
+/// The texture unit of a model's first extra texture. Unit 0 holds its main texture, and unit 1 the shadow map.
+const FIRST_EXTRA_TEXTURE_UNIT: u32 = 2;
+
+/// How far the water plane reaches from the camera in each direction
+const WATER_EXTENT: f32 = 1000.0;
+
+/// A textured quad in the world that always turns to face the camera
+pub struct Billboard {
+    pub position: nalgebra_glm::Vec3,
+    pub size: nalgebra_glm::Vec2,
+    pub texture: TextureId,
+    /// Only rotate about the Z axis, so that the billboard stays upright. Good for trees.
+    pub cylindrical: bool,
+}
+
+impl Billboard {
+    /// Create a new billboard that faces the camera from every angle
+    pub fn new(position: nalgebra_glm::Vec3, size: nalgebra_glm::Vec2, texture: TextureId) -> Self {
+        Self {
+            position,
+            size,
+            texture,
+            cylindrical: false,
+        }
+    }
+
+    /// The model matrix of the billboard, given the camera's right and up directions in world space
+    fn model_matrix(
+        &self,
+        right: nalgebra_glm::Vec3,
+        up: nalgebra_glm::Vec3,
+    ) -> nalgebra_glm::Mat4 {
+        let (right, up) = if self.cylindrical {
+            // Flatten the camera's right direction onto the ground
+            let flat_right = right - WORLD_UP * right.dot(&WORLD_UP);
+            (flat_right.normalize(), WORLD_UP)
+        } else {
+            (right, up)
+        };
+        let facing = right.cross(&up);
+
+        // The quad's texture starts at its +X+Y corner, so X runs to the left to keep the texture upright
+        let x_axis = -right * self.size.x / 2.0;
+        let y_axis = up * self.size.y / 2.0;
+        nalgebra_glm::Mat4::from_columns(&[
+            x_axis.push(0.0),
+            y_axis.push(0.0),
+            facing.push(0.0),
+            self.position.push(1.0),
+        ])
+    }
+}
+
 impl RenderContext {
     pub fn render_3d_models_system(
         &self,
-        world: &mut World,
+        world: &World,
         directional_light: &DirectionalLightSource,
         bvh: &BVH<Entity>,
         debug: bool,
     ) {
-        self.set_program_from_id(self.get_program_id_from_name("3d").unwrap());
+        let program_3d = self.get_program_id_from_name("3d").unwrap();
+        // Built-in variants are added the first time they're needed, unless the host has already added its own
+        let program_for = |mesh: &Mesh, model: &ModelComponent| {
+            if model.alpha_cutout.is_some() {
+                // Discards fragments whose alpha is below `u_alpha_cutout`
+                return self.get_or_add_program("3d-cutout", CUTOUT_VERT, CUTOUT_FRAG);
+            }
+            if mesh.has_tangents() && model.has_normal_map() {
+                // Perturbs normals by the texture bound to `NORMAL_MAP_UNIFORM`, in the tangent space given by the
+                // `GeometryDataIndex::Tangent` attribute
+                return self.get_or_add_program("3d-normalmap", NORMAL_MAP_VERT, NORMAL_MAP_FRAG);
+            }
+            if mesh.has_material_weights() {
+                // Blends the materials of the terrain texture atlas by the `GeometryDataIndex::MaterialWeights`
+                // attribute
+                return self.get_or_add_program("3d-terrain", TERRAIN_VERT, TERRAIN_FRAG);
+            }
+            if mesh.has_colors() {
+                // Tints the texture by the `GeometryDataIndex::Color` attribute
+                return self.get_or_add_program(
+                    "3d-vertex-color",
+                    VERTEX_COLOR_VERT,
+                    VERTEX_COLOR_FRAG,
+                );
+            }
+            program_3d
+        };
+        let mut current_program = program_3d;
+        self.set_program_from_id(program_3d);
+        self.update_camera_block();
 
         // let u_sun_dir = self.get_program_uniform("u_sun_dir").expect("erm lol");
         // unsafe {
@@ -47,25 +334,51 @@ impl RenderContext {
 
         let (light_view_matrix, light_proj_matrix) =
             directional_light.shadow_camera.view_proj_matrices();
-        let u_light_matrix = Uniform::new(self.get_current_program_id(), "light_mvp").unwrap();
         let light_proj_view = light_proj_matrix * light_view_matrix;
-        unsafe {
-            gl::UniformMatrix4fv(
-                u_light_matrix.id,
-                1,
-                gl::FALSE,
-                &light_proj_view.columns(0, 4)[0],
-            );
-        }
-
-        let camera_frustum = &self.camera.borrow().frustum();
+        // Materials and program variants don't have to receive light or shadows, so skip programs without the uniforms
+        let set_light_uniforms = || {
+            if let Ok(u_light_matrix) = self.get_program_uniform("light_mvp") {
+                u_light_matrix.set(&UniformValue::Mat4(light_proj_view));
+            }
+            if let Ok(u_sun_dir) = self.get_program_uniform("u_sun_dir") {
+                u_sun_dir.set(&UniformValue::Vec3(directional_light.light_dir));
+            }
+        };
+        set_light_uniforms();
+        directional_light.activate_framebuffer(self.get_current_program_id());
 
         let (view_matrix, proj_matrix) = self.camera.borrow().view_proj_matrices();
-        for model_id in bvh.iter_frustum(camera_frustum, debug) {
-            let model = world.get::<&mut ModelComponent>(model_id).unwrap();
+        // Group draws by program and then texture, so that each is only bound when it changes. The sort is stable, so
+        // models that share both stay in front-to-back order.
+        let (visible, mut culling_stats) = self.cull_front_to_back(world, bvh, debug);
+        let mut draws: Vec<(Entity, ProgramId, TextureId)> = visible
+            .into_iter()
+            .map(|model_id| {
+                let model = world.get::<&ModelComponent>(model_id).unwrap();
+                let program = match model.material {
+                    Some(material) => self.get_material_from_id(material).unwrap().program,
+                    None => program_for(&self.get_mesh_from_id(model.mesh_id).unwrap(), &model),
+                };
+                (model_id, program, model.texture_id)
+            })
+            .collect();
+        draws.sort_by_key(|(_, program, texture)| (program.as_usize(), texture.as_usize()));
+
+        let mut triangles_rendered = 0;
+        let mut drawn = 0;
+        let mut current_texture = None;
+        for (model_id, program, texture_id) in draws {
+            let model = world.get::<&ModelComponent>(model_id).unwrap();
             let mesh = self.get_mesh_from_id(model.mesh_id).unwrap();
-            let texture = self.get_texture_from_id(model.texture_id).unwrap();
-            let model_matrix = model.get_model_matrix();
+            let model_matrix = model_matrix_of(world, model_id, &model);
+
+            if program.as_usize() != current_program.as_usize() {
+                current_program = program;
+                current_texture = None;
+                self.set_program_from_id(program);
+                set_light_uniforms();
+                directional_light.activate_framebuffer(self.get_current_program_id());
+            }
 
             if model.outlined {
                 unsafe {
@@ -78,17 +391,107 @@ impl RenderContext {
                 }
             }
 
-            texture.activate(gl::TEXTURE0);
-            texture.associate_uniform(self.get_current_program_id(), 0, "texture0");
+            if current_texture != Some(texture_id.as_usize()) {
+                current_texture = Some(texture_id.as_usize());
+                let texture = self.get_texture_from_id(texture_id).unwrap();
+                texture.activate(gl::TEXTURE0);
+                texture.associate_uniform(self.get_current_program_id(), 0, "texture0");
+            }
 
-            directional_light.activate_framebuffer(self.get_current_program_id());
+            for (unit, (texture_id, uniform_name)) in model.extra_textures.iter().enumerate() {
+                let unit = FIRST_EXTRA_TEXTURE_UNIT + unit as u32;
+                let texture = self.get_texture_from_id(*texture_id).unwrap();
+                texture.activate(gl::TEXTURE0 + unit);
+                texture.associate_uniform(self.get_current_program_id(), unit as i32, uniform_name);
+            }
 
-            self.draw(mesh.borrow(), model_matrix, view_matrix, proj_matrix);
+            let mut uniforms = vec![];
+            if let Some(material) = model.material {
+                uniforms.extend_from_slice(&self.get_material_from_id(material).unwrap().uniforms);
+            }
+            if let Some(threshold) = model.alpha_cutout {
+                uniforms.push(("u_alpha_cutout", UniformValue::Float(threshold)));
+            }
+            self.draw_with_uniforms(
+                mesh.borrow(),
+                model_matrix,
+                view_matrix,
+                proj_matrix,
+                &uniforms,
+            );
+            triangles_rendered += mesh.triangle_count();
+            drawn += 1;
         }
-        // println!("{:?}", rendered);
+        *self.triangles_rendered.borrow_mut() = triangles_rendered;
+        culling_stats.drawn = drawn;
+        *self.culling_stats.borrow_mut() = culling_stats;
+    }
+
+    /// The models in the camera's frustum that are visible with the current layer mask, sorted from nearest to farthest
+    /// from the camera by the center of their mesh's AABB.
+    ///
+    /// Drawing opaque models front-to-back lets the depth test reject hidden fragments before they're shaded, which
+    /// cuts down on overdraw in dense scenes. The trade-off is that models sharing a program or texture are no longer
+    /// drawn one after another, so GL state changes more often. `render_3d_models_system` groups these by program and
+    /// texture first, and only keeps front-to-back order within each group.
+    pub fn visible_models_front_to_back(
+        &self,
+        world: &World,
+        bvh: &BVH<Entity>,
+        debug: bool,
+    ) -> Vec<Entity> {
+        self.cull_front_to_back(world, bvh, debug).0
+    }
+
+    /// Does the work of `visible_models_front_to_back`, and also counts how many models were tested, culled, and
+    /// hidden along the way. The stats' `drawn` is left at zero.
+    fn cull_front_to_back(
+        &self,
+        world: &World,
+        bvh: &BVH<Entity>,
+        debug: bool,
+    ) -> (Vec<Entity>, CullingStats) {
+        let camera = self.camera.borrow();
+        let camera_position = camera.position();
+        let layer_mask = *self.layer_mask.borrow();
+
+        let mut stats = CullingStats::default();
+        let mut models: Vec<(Entity, f32)> = bvh
+            .iter_frustum(&camera.frustum(), debug)
+            .filter_map(|model_id| {
+                stats.tested += 1;
+                let model = world.get::<&ModelComponent>(model_id).unwrap();
+                if !model.is_visible_in(layer_mask) {
+                    stats.hidden += 1;
+                    return None;
+                }
+                let local_center = self.get_mesh_aabb(model.mesh_id).center();
+                let center = model_matrix_of(world, model_id, &model) * local_center.push(1.0);
+                Some((
+                    model_id,
+                    nalgebra_glm::distance2(&center.xyz(), &camera_position),
+                ))
+            })
+            .collect();
+        models.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        stats.culled = bvh.len() - stats.tested;
+        (
+            models.into_iter().map(|(model_id, _)| model_id).collect(),
+            stats,
+        )
     }
 
     pub fn render_3d_outlines_system(&self, world: &mut World, bvh: &BVH<Entity>) {
+        // Skip the whole pass, including walking the BVH, when there's nothing to outline
+        let layer_mask = *self.layer_mask.borrow();
+        let any_outlined = world
+            .query::<&ModelComponent>()
+            .iter()
+            .any(|(_, model)| model.outlined && model.is_visible_in(layer_mask));
+        if !any_outlined {
+            return;
+        }
+
         unsafe {
             gl::StencilFunc(gl::NOTEQUAL, 1, 0xFF);
             gl::StencilMask(0x00);
@@ -98,31 +501,30 @@ impl RenderContext {
         self.set_program_from_id(self.get_program_id_from_name("3d-solid").unwrap());
         let camera_frustum = &self.camera.borrow().frustum();
 
-        let u_color = self.get_program_uniform("u_color").unwrap();
-        unsafe {
-            gl::Uniform4f(
-                u_color.id,
-                self.color.borrow().x,
-                self.color.borrow().y,
-                self.color.borrow().z,
-                self.color.borrow().w,
-            );
-        }
+        let default_color = *self.color.borrow();
 
         let (view_matrix, proj_matrix) = self.camera.borrow().view_proj_matrices();
         for model_id in bvh.iter_frustum(camera_frustum, false) {
-            let mut model = world.get::<&mut ModelComponent>(model_id).unwrap();
-            if !model.outlined {
+            let model = world.get::<&ModelComponent>(model_id).unwrap();
+            if !model.outlined || !model.is_visible_in(layer_mask) {
                 continue;
             }
             let mesh = self.get_mesh_from_id(model.mesh_id).unwrap();
 
-            let old_scale = model.get_scale();
-            model.set_scale(old_scale * 1.2);
-            let model_matrix = model.get_model_matrix();
-            model.set_scale(old_scale);
+            let outline_scale = 1.0 + model.outline_width;
+            let model_matrix = nalgebra_glm::scale(
+                &model_matrix_of(world, model_id, &model),
+                &nalgebra_glm::vec3(outline_scale, outline_scale, outline_scale),
+            );
+            let color = model.outline_color.unwrap_or(default_color);
 
-            self.draw(mesh.borrow(), model_matrix, view_matrix, proj_matrix);
+            self.draw_with_uniforms(
+                mesh.borrow(),
+                model_matrix,
+                view_matrix,
+                proj_matrix,
+                &[("u_color", UniformValue::Vec4(color))],
+            );
         }
 
         unsafe {
@@ -132,6 +534,168 @@ impl RenderContext {
         }
     }
 
+    /// Draws the volume of every node in a BVH as a wireframe box, down to a maximum depth. Leaves are drawn in green,
+    /// and internal nodes are colored by their depth.
+    pub fn render_bvh_debug<Object: Copy + Clone>(&self, bvh: &BVH<Object>, max_depth: usize) {
+        const DEPTH_COLORS: [nalgebra_glm::Vec4; 4] = [
+            nalgebra_glm::Vec4::new(1.0, 0.2, 0.2, 1.0),
+            nalgebra_glm::Vec4::new(1.0, 0.6, 0.2, 1.0),
+            nalgebra_glm::Vec4::new(1.0, 1.0, 0.2, 1.0),
+            nalgebra_glm::Vec4::new(0.2, 0.6, 1.0, 1.0),
+        ];
+        let leaf_color = nalgebra_glm::vec4(0.2, 1.0, 0.2, 1.0);
+
+        // Batch the boxes by color, so that each color is one draw call
+        let mut leaves = vec![];
+        let mut internal_nodes = vec![vec![]; DEPTH_COLORS.len()];
+        for (volume, depth, is_leaf) in bvh.debug_volumes(max_depth) {
+            if is_leaf {
+                leaves.push(volume.corners());
+            } else {
+                internal_nodes[depth % DEPTH_COLORS.len()].push(volume.corners());
+            }
+        }
+        for (boxes, color) in internal_nodes.iter().zip(DEPTH_COLORS) {
+            self.draw_box_edges(boxes, color);
+        }
+        self.draw_box_edges(&leaves, leaf_color);
+    }
+
+    /// Draws an AABB as a wireframe box, using the current camera
+    pub fn draw_aabb(&self, aabb: &AABB, color: nalgebra_glm::Vec4) {
+        self.draw_box_edges(&[aabb.corners()], color);
+    }
+
+    /// Draws a frustum as a wireframe, using the current camera. Useful for seeing what another camera can see.
+    pub fn draw_frustum(&self, frustum: &Frustum, color: nalgebra_glm::Vec4) {
+        // Frustum corners are ordered left/right, bottom/top, near/far, which matches the AABB corner ordering
+        self.draw_box_edges(&[frustum.corners()], color);
+    }
+
+    /// Draws a translucent water surface at `sea_level` that reaches out to the horizon around `camera`. `seconds` drives
+    /// the waves. Draw this after opaque geometry, so that the terrain shows through.
+    pub fn render_water(&self, sea_level: f32, camera: &Camera, seconds: f32) {
+        let center = camera.position();
+        let mut water_quad = self.water_quad.borrow_mut();
+        let (vbo, vao) = water_quad.get_or_insert_with(|| {
+            let vbo = Buffer::<f32>::gen(gl::ARRAY_BUFFER);
+            vbo.set_data(&vec![
+                -1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0, 1.0, 0.0, //
+                -1.0, -1.0, 0.0, 1.0, 1.0, 0.0, -1.0, 1.0, 0.0,
+            ]);
+            let vao = Vao::gen();
+            vao.set(0);
+            vao.unbind();
+            vbo.unbind();
+            (vbo, vao)
+        });
+        // Centered under the camera, so that it always reaches the horizon
+        let model_matrix = nalgebra_glm::scale(
+            &nalgebra_glm::translation(&nalgebra_glm::vec3(center.x, center.y, sea_level)),
+            &nalgebra_glm::vec3(WATER_EXTENT, WATER_EXTENT, 1.0),
+        );
+
+        let program_id = self.get_or_add_program("water", WATER_VERT, WATER_FRAG);
+        self.set_program_from_id(program_id);
+
+        let (view_matrix, proj_matrix) = camera.view_proj_matrices();
+        let uniforms = [
+            ("u_model_matrix", UniformValue::Mat4(model_matrix)),
+            ("u_view_matrix", UniformValue::Mat4(view_matrix)),
+            ("u_proj_matrix", UniformValue::Mat4(proj_matrix)),
+            ("u_time", UniformValue::Float(seconds)),
+            ("u_camera_pos", UniformValue::Vec3(center)),
+            (
+                "u_color",
+                UniformValue::Vec4(nalgebra_glm::vec4(0.15, 0.35, 0.5, 0.6)),
+            ),
+        ];
+        for (name, value) in uniforms.iter() {
+            if let Ok(uniform) = self.get_program_uniform(name) {
+                uniform.set(value);
+            }
+        }
+
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+            // Visible from underneath too
+            gl::Disable(gl::CULL_FACE);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            // Translucent, so don't hide anything drawn after
+            gl::DepthMask(gl::FALSE);
+            gl::BindVertexArray(vao.id);
+            vbo.bind();
+            vao.enable(0);
+            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            vbo.unbind();
+            vao.unbind();
+            gl::DepthMask(gl::TRUE);
+            gl::Enable(gl::CULL_FACE);
+        }
+    }
+
+    /// Draws the 12 edges of each box as lines in one draw call, using the current camera. The corners are indexed so
+    /// that bit 0 selects the x side, bit 1 the y side, and bit 2 the z side, like `AABB::corners`.
+    fn draw_box_edges(&self, boxes: &[[nalgebra_glm::Vec3; 8]], color: nalgebra_glm::Vec4) {
+        if boxes.is_empty() {
+            return;
+        }
+        let mut vertices = Vec::<f32>::with_capacity(boxes.len() * 12 * 2 * 3);
+        for corners in boxes {
+            for i in 0..8 {
+                for bit in [1, 2, 4] {
+                    if i & bit == 0 {
+                        for corner in [corners[i], corners[i | bit]] {
+                            vertices.push(corner.x);
+                            vertices.push(corner.y);
+                            vertices.push(corner.z);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut line_buffer = self.line_buffer.borrow_mut();
+        let (vbo, vao) =
+            line_buffer.get_or_insert_with(|| (Buffer::gen(gl::ARRAY_BUFFER), Vao::gen()));
+        vbo.set_data(&vertices);
+        vao.set(0);
+
+        self.set_program(Some("line"));
+        let (view_matrix, proj_matrix) = self.camera.borrow().view_proj_matrices();
+        let model_matrix: nalgebra_glm::Mat4 = nalgebra_glm::one();
+        let u_model_matrix = self.get_program_uniform("model").unwrap();
+        let u_view_matrix = self.get_program_uniform("view").unwrap();
+        let u_proj_matrix = self.get_program_uniform("projection").unwrap();
+        let u_color = self.get_program_uniform("u_color").unwrap();
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+            gl::UniformMatrix4fv(
+                u_model_matrix.id,
+                1,
+                gl::FALSE,
+                &model_matrix.columns(0, 4)[0],
+            );
+            gl::UniformMatrix4fv(
+                u_view_matrix.id,
+                1,
+                gl::FALSE,
+                &view_matrix.columns(0, 4)[0],
+            );
+            gl::UniformMatrix4fv(
+                u_proj_matrix.id,
+                1,
+                gl::FALSE,
+                &proj_matrix.columns(0, 4)[0],
+            );
+            gl::Uniform4f(u_color.id, color.x, color.y, color.z, color.w);
+            gl::DrawArrays(gl::LINES, 0, (vertices.len() / 3) as i32);
+        }
+        vao.unbind();
+        vbo.unbind();
+    }
+
     pub fn render_3d_line_paths(&self, world: &World) {
         unsafe {
             gl::Enable(gl::BLEND);
@@ -142,4 +706,44 @@ impl RenderContext {
             self.draw_line_path(line_path, view_matrix, proj_matrix)
         }
     }
+
+    /// Draws every `Billboard` in the world, turned to face the camera
+    pub fn render_billboards_system(&self, world: &World) {
+        self.set_program_from_id(self.get_program_id_from_name("2d").unwrap());
+        unsafe {
+            // Billboards are mirrored to keep their textures upright, so they would be culled as back faces
+            gl::Disable(gl::CULL_FACE);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+
+        // Billboards show the whole texture
+        let u_sprite_offset = self.get_program_uniform("u_sprite_offset").unwrap();
+        let u_sprite_size = self.get_program_uniform("u_sprite_size").unwrap();
+        unsafe {
+            gl::Uniform2f(u_sprite_offset.id, 0.0, 0.0);
+            gl::Uniform2f(u_sprite_size.id, 1.0, 1.0);
+        }
+
+        let (view_matrix, proj_matrix) = self.camera.borrow().view_proj_matrices();
+        let right = self.camera.borrow().right();
+        let up = self.camera.borrow().true_up();
+
+        let quad_mesh = self
+            .get_mesh_from_id(self.get_mesh_id_from_name("quad-xy").unwrap())
+            .unwrap();
+        for (_entity, billboard) in world.query::<&Billboard>().iter() {
+            let texture = self.get_texture_from_id(billboard.texture).unwrap();
+            texture.activate(gl::TEXTURE0);
+            texture.associate_uniform(self.get_current_program_id(), 0, "texture0");
+
+            let model_matrix = billboard.model_matrix(right, up);
+            self.draw(quad_mesh.borrow(), model_matrix, view_matrix, proj_matrix);
+        }
+
+        unsafe {
+            gl::Enable(gl::CULL_FACE);
+        }
+    }
 }
@@ -2,34 +2,167 @@
 
 use std::{cell::RefCell, collections::HashMap, f32::consts::PI, fmt::Debug};
 
-use obj::{load_obj, Obj, TexturedVertex};
-
 use super::{
     aabb::AABB,
-    camera::{Camera, ProjectionKind},
+    camera::{Camera, ProjectionKind, WORLD_UP},
     font::{Font, FontId, FontManager},
-    opengl::{Buffer, Program, Texture, Uniform, Vao},
+    mesh_data::MeshData,
+    opengl::{
+        create_program, Buffer, Fbo, FboScope, MultisampleFbo, Program, Texture, Uniform,
+        UniformBuffer, UniformValue, Vao,
+    },
+    perlin::HeightMap,
+    rectangle::Rectangle,
+    render2d::SpriteBatch,
 };
 
+/// A vertex shader for `draw_fullscreen`, which passes the fullscreen triangle through and gives each fragment its
+/// texture coordinate on the screen in `v_uv`
+pub const FULLSCREEN_VERT: &str = r#"
+#version 330 core
+layout (location = 0) in vec2 a_pos;
+out vec2 v_uv;
+
+void main() {
+    v_uv = a_pos * 0.5 + 0.5;
+    gl_Position = vec4(a_pos, 0.0, 1.0);
+}
+"#;
+
+/// A vertex shader for `NORMALS_GEOM`, which passes each vertex's world-space position and normal through. Draw with
+/// `draw_with_uniforms`, which sets the matrices.
+pub const NORMALS_VERT: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_pos;
+layout (location = 1) in vec3 a_normal;
+
+uniform mat4 u_model_matrix;
+uniform mat3 u_normal_matrix;
+
+out vec3 v_normal;
+
+void main() {
+    v_normal = normalize(u_normal_matrix * a_normal);
+    gl_Position = u_model_matrix * vec4(a_pos, 1.0);
+}
+"#;
+
+/// An example geometry shader for `create_program_geom`, which turns each triangle into a line per vertex, drawn
+/// `u_normal_length` units along its normal. Useful for checking a mesh's normals.
+pub const NORMALS_GEOM: &str = r#"
+#version 330 core
+layout (triangles) in;
+layout (line_strip, max_vertices = 6) out;
+
+in vec3 v_normal[];
+
+uniform mat4 u_view_matrix;
+uniform mat4 u_proj_matrix;
+uniform float u_normal_length;
+
+void main() {
+    mat4 view_proj = u_proj_matrix * u_view_matrix;
+    for (int i = 0; i < 3; i++) {
+        vec4 base = gl_in[i].gl_Position;
+        gl_Position = view_proj * base;
+        EmitVertex();
+        gl_Position = view_proj * (base + vec4(v_normal[i] * u_normal_length, 0.0));
+        EmitVertex();
+        EndPrimitive();
+    }
+}
+"#;
+
+/// A fragment shader for `NORMALS_GEOM`, which draws the lines in `u_color`
+pub const NORMALS_FRAG: &str = r#"
+#version 330 core
+uniform vec4 u_color;
+out vec4 frag_color;
+
+void main() {
+    frag_color = u_color;
+}
+"#;
+
+/// The layer mask that matches every layer
+pub const ALL_LAYERS: u32 = u32::MAX;
+
+/// The layer that models are on when they're created
+pub const DEFAULT_LAYER: u32 = 1;
+
+/// The binding point of the uniform buffer that holds the 3D camera. Programs can read the camera from it by declaring:
+/// ```glsl
+/// layout (std140) uniform Camera {
+///     mat4 u_view_matrix;
+///     mat4 u_proj_matrix;
+///     vec4 u_camera_pos;
+/// };
+/// ```
+pub const CAMERA_BLOCK_BINDING: u32 = 0;
+
+/// The name of the uniform block that holds the 3D camera
+const CAMERA_BLOCK_NAME: &str = "Camera";
+
+/// The size of the camera uniform block, in bytes. Two mat4s and a vec4.
+const CAMERA_BLOCK_SIZE: usize = (16 + 16 + 4) * std::mem::size_of::<f32>();
+
 pub struct RenderContext {
     // Updated by the user
     pub camera: RefCell<Camera>,
     pub program: RefCell<Option<ProgramId>>,
     pub color: RefCell<nalgebra_glm::Vec4>,
     pub font: RefCell<Option<FontId>>,
+    /// Only models on at least one of these layers are drawn by the 3D systems
+    pub layer_mask: RefCell<u32>,
 
     // Managers
     mesh_manager: RefCell<ResourceManager<Mesh, MeshId>>,
     texture_manager: RefCell<ResourceManager<Texture, TextureId>>,
     program_manager: RefCell<ResourceManager<Program, ProgramId>>,
+    material_manager: RefCell<ResourceManager<Material, MaterialId>>,
     font_manager: RefCell<FontManager>,
 
+    // Clipping rectangles for 2D rendering, innermost last
+    scissor_stack: RefCell<Vec<Rectangle>>,
+
+    // Textured 2D quads waiting to be drawn
+    pub(crate) sprite_batch: RefCell<SpriteBatch>,
+
+    // Offscreen target for `render_to_texture`, with a depth buffer that is resized to match the target texture
+    render_target: Fbo,
+    render_target_depth: RefCell<(Texture, (i32, i32))>,
+    render_target_msaa: RefCell<Option<MultisampleFbo>>,
+    // What `render_to_texture_multisampled` resolves through, kept apart from `render_target` so that `f` can render to
+    // a texture as well without changing where the resolve ends up
+    render_target_resolve: Fbo,
+
+    // Shared with every program that declares the camera block, so the camera only has to be uploaded once per frame
+    camera_ubo: UniformBuffer,
+
+    // Whether `GL_FRAMEBUFFER_SRGB` is enabled, and color textures are loaded as sRGB
+    srgb: RefCell<bool>,
+
+    // A single triangle that covers the whole screen, created the first time `draw_fullscreen` is called
+    fullscreen_triangle: RefCell<Option<(Buffer<f32>, Vao)>>,
+
+    // Vertices for debug lines, created the first time a box is drawn and refilled for each batch of boxes
+    pub(crate) line_buffer: RefCell<Option<(Buffer<f32>, Vao)>>,
+
+    // A square from -1 to 1 on the XY plane, created the first time `render_water` is called and moved by its model
+    // matrix
+    pub(crate) water_quad: RefCell<Option<(Buffer<f32>, Vao)>>,
+
+    // Statistics from the last call to `render_3d_models_system`
+    pub(crate) triangles_rendered: RefCell<usize>,
+    pub(crate) culling_stats: RefCell<CullingStats>,
+
     // Updated by the app
     pub int_screen_resolution: nalgebra_glm::I32Vec2,
-    pub camera_2d: Camera,
+    /// Camera for 2D rendering, in pixels from the top-left corner of the screen
+    pub camera_2d: RefCell<Camera>,
 }
 
-struct ResourceManager<Resource, Id: OpaqueId> {
+pub(crate) struct ResourceManager<Resource, Id: OpaqueId> {
     resources: Vec<Resource>,
     keys: HashMap<&'static str, Id>,
 }
@@ -51,6 +184,16 @@ pub struct TextureId(usize);
 #[derive(Copy, Clone, Debug)]
 pub struct ProgramId(usize);
 
+/// Opaque type used by the material manager to associate materials.
+#[derive(Copy, Clone, Debug)]
+pub struct MaterialId(usize);
+
+/// A program to draw models with, along with uniforms that are uploaded whenever a model with the material is drawn
+pub struct Material {
+    pub program: ProgramId,
+    pub uniforms: Vec<(&'static str, UniformValue)>,
+}
+
 /// An actual model, with geometry, a position, scale, rotation, and texture.
 pub struct ModelComponent {
     pub mesh_id: MeshId,
@@ -58,8 +201,36 @@ pub struct ModelComponent {
     position: nalgebra_glm::Vec3,
     scale: nalgebra_glm::Vec3,
     model_matrix: nalgebra_glm::Mat4,
+    /// Whether the model is drawn at all
     pub shown: bool,
+    /// The layers the model is on, as bits. The model is only drawn when the renderer's layer mask shares a bit
+    /// with it.
+    pub layer_mask: u32,
     pub outlined: bool,
+    /// If set, the model is drawn with this material instead of a program picked from its mesh and settings
+    pub material: Option<MaterialId>,
+    /// The color of the model's outline. If unset, the renderer's current color is used.
+    pub outline_color: Option<nalgebra_glm::Vec4>,
+    /// How much larger the outline is than the model, as a fraction of the model's size
+    pub outline_width: f32,
+    /// If set, fragments whose texture alpha is below this threshold are discarded instead of drawn, for foliage
+    pub alpha_cutout: Option<f32>,
+    /// Textures bound in addition to `texture_id`, like detail or normal maps, along with the name of the sampler
+    /// uniform each is bound to
+    pub extra_textures: Vec<(TextureId, &'static str)>,
+}
+
+/// How many models were frustum culled while rendering a frame
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CullingStats {
+    /// Models that the BVH found in the camera's frustum
+    pub tested: usize,
+    /// Models that the BVH rejected, because they were outside of the frustum
+    pub culled: usize,
+    /// Models in the frustum that weren't drawn, because they were hidden or not on a layer in the layer mask
+    pub hidden: usize,
+    /// Models that were drawn
+    pub drawn: usize,
 }
 
 pub struct LinePathComponent {
@@ -76,7 +247,12 @@ pub struct LinePathComponent {
 /// multiple models.
 pub struct Mesh {
     geometry: Vec<GeometryData>,
+    // Tangents are optional and always at `GeometryDataIndex::Tangent`, so they're kept apart from the other attributes
+    tangents: Option<GeometryData>,
     indices: Vec<u32>,
+    // Meshes without indices draw their vertices in order
+    indexed: bool,
+    primitive: gl::types::GLenum,
     aabb: AABB,
 }
 
@@ -86,6 +262,24 @@ struct GeometryData {
     vbo: Buffer<f32>,
     vao: Vao,
     vertex_data: Vec<f32>,
+    components: i32,
+}
+
+impl GeometryData {
+    /// Allocates VRAM buffers for an attribute at a location, leaving them bound. This is slow!
+    fn new(data: &[f32], components: i32, location: u32, indices: &Vec<u32>) -> Self {
+        let retval = Self {
+            ibo: Buffer::<u32>::gen(gl::ELEMENT_ARRAY_BUFFER),
+            vao: Vao::gen_with_components(components),
+            vbo: Buffer::<f32>::gen(gl::ARRAY_BUFFER),
+            vertex_data: data.to_vec(),
+            components,
+        };
+        retval.vbo.set_data(&retval.vertex_data);
+        retval.ibo.set_data(indices);
+        retval.vao.set(location);
+        retval
+    }
 }
 
 pub enum GeometryDataIndex {
@@ -93,8 +287,13 @@ pub enum GeometryDataIndex {
     Normal = 1,
     Texture = 2,
     Color = 3,
+    MaterialWeights = 4,
+    Tangent = 5,
 }
 
+/// The sampler uniform that a model's normal map is bound to
+pub const NORMAL_MAP_UNIFORM: &str = "normal_map";
+
 impl RenderContext {
     pub fn new() -> Self {
         let retval = Self {
@@ -102,26 +301,35 @@ impl RenderContext {
             program: RefCell::new(None),
             color: RefCell::new(nalgebra_glm::vec4(0.0, 0.0, 0.0, 1.0)),
             font: RefCell::new(None),
+            layer_mask: RefCell::new(ALL_LAYERS),
 
             mesh_manager: RefCell::new(ResourceManager::new()),
             texture_manager: RefCell::new(ResourceManager::new()),
             program_manager: RefCell::new(ResourceManager::new()),
+            material_manager: RefCell::new(ResourceManager::new()),
             font_manager: RefCell::new(FontManager::new()),
 
+            scissor_stack: RefCell::new(vec![]),
+
+            sprite_batch: RefCell::new(SpriteBatch::new()),
+
+            render_target: Fbo::new(),
+            render_target_depth: RefCell::new((Texture::new(), (0, 0))),
+            render_target_msaa: RefCell::new(None),
+            render_target_resolve: Fbo::new(),
+
+            camera_ubo: UniformBuffer::new(CAMERA_BLOCK_BINDING, CAMERA_BLOCK_SIZE),
+
+            srgb: RefCell::new(false),
+            fullscreen_triangle: RefCell::new(None),
+            line_buffer: RefCell::new(None),
+            water_quad: RefCell::new(None),
+
+            triangles_rendered: RefCell::new(0),
+            culling_stats: RefCell::new(CullingStats::default()),
+
             int_screen_resolution: nalgebra_glm::I32Vec2::new(0, 0),
-            camera_2d: Camera::new(
-                nalgebra_glm::vec3(0.0, 0.0, 0.0),
-                nalgebra_glm::vec3(0.0, 0.0, 1.0),
-                nalgebra_glm::vec3(0.0, 1.0, 0.0),
-                ProjectionKind::Orthographic {
-                    left: -1.0,
-                    right: 1.0,
-                    bottom: -1.0,
-                    top: 1.0,
-                    near: 0.1,
-                    far: 10.0,
-                },
-            ),
+            camera_2d: RefCell::new(Self::pixel_camera(1, 1)),
         };
 
         // TODO: Add meshes
@@ -130,8 +338,51 @@ impl RenderContext {
         retval
     }
 
+    /// Sets the size of the screen, in pixels. The 2D camera is fit to the new size.
+    pub fn set_screen_resolution(&mut self, resolution: nalgebra_glm::I32Vec2) {
+        if self.int_screen_resolution != resolution {
+            self.int_screen_resolution = resolution;
+            self.set_2d_viewport(resolution.x, resolution.y);
+        }
+    }
+
+    /// Fits the 2D camera to an area `width` by `height` pixels, with the origin in the top-left corner. This happens
+    /// automatically when the screen is resized, but can be used to lay out 2D rendering for a render target.
+    pub fn set_2d_viewport(&self, width: i32, height: i32) {
+        *self.camera_2d.borrow_mut() = Self::pixel_camera(width, height);
+    }
+
+    fn pixel_camera(width: i32, height: i32) -> Camera {
+        Camera::new(
+            nalgebra_glm::vec3(0.0, 0.0, 0.0),
+            nalgebra_glm::vec3(0.0, 0.0, -1.0),
+            nalgebra_glm::vec3(0.0, 1.0, 0.0),
+            ProjectionKind::Orthographic {
+                left: 0.0,
+                right: width.max(1) as f32,
+                bottom: height.max(1) as f32,
+                top: 0.0,
+                near: 0.1,
+                far: 10.0,
+            },
+        )
+    }
+
     pub fn set_camera(&self, camera: Camera) {
-        *self.camera.borrow_mut() = camera
+        *self.camera.borrow_mut() = camera;
+        self.update_camera_block();
+    }
+
+    /// Uploads the 3D camera to the camera uniform block. Call this after changing `camera` directly.
+    pub fn update_camera_block(&self) {
+        let camera = self.camera.borrow();
+        let (view_matrix, proj_matrix) = camera.view_proj_matrices();
+        let position = camera.position();
+        let mut data = Vec::with_capacity(CAMERA_BLOCK_SIZE / std::mem::size_of::<f32>());
+        data.extend_from_slice(view_matrix.as_slice());
+        data.extend_from_slice(proj_matrix.as_slice());
+        data.extend_from_slice(&[position.x, position.y, position.z, 1.0]);
+        self.camera_ubo.set_data(&data);
     }
 
     pub fn set_program(&self, name: Option<&'static str>) {
@@ -156,6 +407,11 @@ impl RenderContext {
         program.set();
     }
 
+    /// Sets which layers of models the 3D systems draw. Use `ALL_LAYERS` to draw every model.
+    pub fn set_layer_mask(&self, mask: u32) {
+        *self.layer_mask.borrow_mut() = mask;
+    }
+
     pub fn set_color(&self, color: nalgebra_glm::Vec4) {
         let mut color_ref = self.color.borrow_mut();
         *color_ref = color;
@@ -165,12 +421,142 @@ impl RenderContext {
         *self.font.borrow_mut() = Some(font);
     }
 
+    /// Clips all further rendering to a rectangle in screen space, intersected with any enclosing clip rectangles
+    pub fn push_scissor(&self, rect: Rectangle) {
+        self.flush_2d();
+        let clipped = match self.scissor_stack.borrow().last() {
+            Some(outer) => outer
+                .intersection(&rect)
+                .unwrap_or(Rectangle::new(rect.pos.x, rect.pos.y, 0.0, 0.0)),
+            None => rect,
+        };
+        self.scissor_stack.borrow_mut().push(clipped);
+        self.apply_scissor();
+    }
+
+    /// Removes the innermost clip rectangle. Clipping is disabled when no clip rectangles remain.
+    pub fn pop_scissor(&self) {
+        self.flush_2d();
+        self.scissor_stack.borrow_mut().pop();
+        self.apply_scissor();
+    }
+
+    fn apply_scissor(&self) {
+        match self.scissor_stack.borrow().last() {
+            Some(rect) => unsafe {
+                // Scissor boxes are measured from the bottom-left of the screen, rectangles from the top-left
+                gl::Enable(gl::SCISSOR_TEST);
+                gl::Scissor(
+                    rect.pos.x as i32,
+                    self.int_screen_resolution.y - (rect.pos.y + rect.size.y) as i32,
+                    rect.size.x as i32,
+                    rect.size.y as i32,
+                );
+            },
+            None => unsafe {
+                gl::Disable(gl::SCISSOR_TEST);
+            },
+        }
+    }
+
+    /// Renders everything drawn in `f` into the `target` texture instead of the screen. The viewport is set to the size
+    /// of the texture while `f` runs, and the framebuffer and viewport that were current before are restored
+    /// afterwards. `f` may use `render_to_texture_multisampled`, but not `render_to_texture`, since both calls would
+    /// share the same offscreen target.
+    pub fn render_to_texture(&self, target: TextureId, f: impl FnOnce()) -> Result<(), String> {
+        let _scope = FboScope::save();
+        let (width, height) = {
+            let texture = self
+                .get_texture_from_id(target)
+                .ok_or("Render target texture does not exist")?;
+            let dimensions = texture
+                .get_dimensions()
+                .ok_or("Render target texture has no storage")?;
+            self.render_target.attach_color(&texture);
+            dimensions
+        };
+
+        {
+            let mut depth = self.render_target_depth.borrow_mut();
+            if depth.1 != (width, height) {
+                depth.0.load_depth_buffer(width, height);
+                depth.1 = (width, height);
+            }
+            self.render_target.attach_depth(&depth.0);
+        }
+
+        self.render_target.check_complete()?;
+
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+        }
+        f();
+        self.flush_2d();
+        Ok(())
+    }
+
+    /// Like `render_to_texture`, but renders into a multisampled buffer with `samples` samples per pixel first, which
+    /// is then resolved into the `target` texture. This smooths out aliased edges. `f` may render to other textures
+    /// with either function.
+    pub fn render_to_texture_multisampled(
+        &self,
+        target: TextureId,
+        samples: i32,
+        f: impl FnOnce(),
+    ) -> Result<(), String> {
+        let _scope = FboScope::save();
+        let (width, height) = {
+            let texture = self
+                .get_texture_from_id(target)
+                .ok_or("Render target texture does not exist")?;
+            texture
+                .get_dimensions()
+                .ok_or("Render target texture has no storage")?
+        };
+
+        // Taken out of the cell while rendering, since `f` may render to a multisampled texture as well
+        let msaa = match self.render_target_msaa.take() {
+            Some(msaa) if msaa.size() == (width, height) && msaa.samples() == samples => msaa,
+            _ => MultisampleFbo::new(width, height, samples)?,
+        };
+
+        msaa.bind();
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+        }
+        f();
+        self.flush_2d();
+
+        // Attached only once `f` is done, since a multisampled render inside `f` attaches its own target here
+        let complete = self
+            .get_texture_from_id(target)
+            .ok_or_else(|| String::from("Render target texture does not exist"))
+            .and_then(|texture| {
+                self.render_target_resolve.attach_color(&texture);
+                self.render_target_resolve.check_complete()
+            });
+        if complete.is_ok() {
+            msaa.resolve_into(&self.render_target_resolve);
+        }
+        *self.render_target_msaa.borrow_mut() = Some(msaa);
+        complete
+    }
+
     pub fn add_mesh(&self, mesh: Mesh, name: Option<&'static str>) -> MeshId {
         self.mesh_manager.borrow_mut().add(mesh, name)
     }
 
-    pub fn add_mesh_from_obj(&self, obj_file_data: &[u8], name: Option<&'static str>) -> MeshId {
-        self.add_mesh(Mesh::from_obj(obj_file_data), name)
+    /// Uploads a mesh from CPU-side data, like a mesh loaded from a cache with `MeshData::from_bytes`
+    pub fn add_mesh_from_data(&self, data: &MeshData, name: Option<&'static str>) -> MeshId {
+        self.add_mesh(Mesh::from_data(data), name)
+    }
+
+    pub fn add_mesh_from_obj(
+        &self,
+        obj_file_data: &[u8],
+        name: Option<&'static str>,
+    ) -> Result<MeshId, String> {
+        Ok(self.add_mesh(Mesh::from_obj(obj_file_data)?, name))
     }
 
     pub fn add_mesh_from_verts(
@@ -182,25 +568,58 @@ impl RenderContext {
         self.add_mesh(Mesh::new(indices, datas), name)
     }
 
+    /// Replaces the vertex positions of a mesh, like after terrain is edited, and re-fits its AABB. See
+    /// `Mesh::set_positions`. Models using the mesh keep their old volumes in the BVH until they're moved.
+    pub fn update_mesh_positions(&self, id: MeshId, positions: &[f32]) -> Result<(), String> {
+        self.mesh_manager
+            .borrow_mut()
+            .get_mut_from_id(id)
+            .ok_or(format!("no mesh with id {:?}", id))?
+            .set_positions(positions)
+    }
+
     pub fn add_texture(&self, texture: Texture, name: Option<&'static str>) -> TextureId {
         self.texture_manager.borrow_mut().add(texture, name)
     }
 
+    /// Adds a texture from a PNG. While sRGB is on, the texture is assumed to hold colors and is loaded as sRGB.
     pub fn add_texture_from_png(
         &self,
         texture_filename: &'static str,
         name: Option<&'static str>,
     ) -> TextureId {
-        self.texture_manager
-            .borrow_mut()
-            .add(Texture::from_png(texture_filename), name)
+        let texture = if *self.srgb.borrow() {
+            Texture::from_png_srgb(texture_filename)
+        } else {
+            Texture::from_png(texture_filename)
+        };
+        self.texture_manager.borrow_mut().add(texture, name)
+    }
+
+    pub fn add_material(&self, material: Material, name: Option<&'static str>) -> MaterialId {
+        self.material_manager.borrow_mut().add(material, name)
     }
 
     pub fn add_program(&self, program: Program, name: Option<&'static str>) -> ProgramId {
+        self.camera_ubo.bind_block(program.id(), CAMERA_BLOCK_NAME);
         let retval = self.program_manager.borrow_mut().add(program, name);
         retval
     }
 
+    /// Gets the program with a name, compiling it from built-in shader sources and adding it the first time. Hosts can
+    /// replace a built-in program by adding their own under the same name first.
+    pub(crate) fn get_or_add_program(
+        &self,
+        name: &'static str,
+        vert: &'static str,
+        frag: &'static str,
+    ) -> ProgramId {
+        match self.get_program_id_from_name(name) {
+            Some(program_id) => program_id,
+            None => self.add_program(create_program(vert, frag).unwrap(), Some(name)),
+        }
+    }
+
     pub fn add_font(
         &self,
         path: &'static str,
@@ -267,6 +686,10 @@ impl RenderContext {
         }
     }
 
+    pub fn get_material_from_id(&self, id: MaterialId) -> Option<std::cell::Ref<'_, Material>> {
+        std::cell::Ref::filter_map(self.material_manager.borrow(), |m| m.get_from_id(id)).ok()
+    }
+
     pub fn get_font_from_id(&self, id: FontId) -> Option<std::cell::Ref<'_, Font>> {
         let manager = self.font_manager.borrow();
         if let Some(_font) = manager.get_font_from_id(id) {
@@ -291,6 +714,10 @@ impl RenderContext {
         self.program_manager.borrow().get_id_from_name(name)
     }
 
+    pub fn get_material_id_from_name(&self, name: &'static str) -> Option<MaterialId> {
+        self.material_manager.borrow().get_id_from_name(name)
+    }
+
     pub fn get_font_id_from_name(&self, name: &'static str) -> Option<FontId> {
         self.font_manager.borrow().get_id_from_name(name)
     }
@@ -313,6 +740,63 @@ impl RenderContext {
             .translate(model.position)
     }
 
+    /// How many triangles were drawn by the last call to `render_3d_models_system`
+    pub fn total_triangles_rendered(&self) -> usize {
+        *self.triangles_rendered.borrow()
+    }
+
+    /// How many models were tested, culled, hidden, and drawn by the last call to `render_3d_models_system`
+    pub fn last_frame_culling_stats(&self) -> CullingStats {
+        *self.culling_stats.borrow()
+    }
+
+    /// Turns sRGB output on or off. While it's on, shaders work in linear color, colors written to the screen are
+    /// converted to sRGB by OpenGL, and textures added with `add_texture_from_png` are converted from sRGB when
+    /// sampled.
+    pub fn set_srgb(&self, on: bool) {
+        unsafe {
+            if on {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            } else {
+                gl::Disable(gl::FRAMEBUFFER_SRGB);
+            }
+        }
+        *self.srgb.borrow_mut() = on;
+    }
+
+    /// Draws a triangle that covers the whole screen with the current program, for screen-space passes. The triangle's
+    /// clip-space position is at location 0, see `FULLSCREEN_VERT`. Depth testing and writing are off while it's drawn.
+    pub fn draw_fullscreen(&self) {
+        let mut fullscreen_triangle = self.fullscreen_triangle.borrow_mut();
+        let (vbo, vao) = fullscreen_triangle.get_or_insert_with(|| {
+            let vbo = Buffer::<f32>::gen(gl::ARRAY_BUFFER);
+            vbo.set_data(&vec![-1.0, -1.0, 3.0, -1.0, -1.0, 3.0]);
+            let vao = Vao::gen_with_components(2);
+            vao.set(0);
+            vao.unbind();
+            vbo.unbind();
+            (vbo, vao)
+        });
+
+        unsafe {
+            let depth_test = gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE;
+            gl::Disable(gl::DEPTH_TEST);
+            gl::DepthMask(gl::FALSE);
+
+            gl::BindVertexArray(vao.id);
+            vbo.bind();
+            vao.enable(0);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            vbo.unbind();
+            vao.unbind();
+
+            gl::DepthMask(gl::TRUE);
+            if depth_test {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+        }
+    }
+
     pub fn get_current_program_id(&self) -> u32 {
         if self.program.borrow().is_some() {
             let program = self
@@ -335,50 +819,41 @@ impl RenderContext {
         view_matrix: nalgebra_glm::Mat4,
         proj_matrix: nalgebra_glm::Mat4,
     ) {
-        let u_model_matrix: Uniform = self.get_program_uniform("u_model_matrix").unwrap();
-        let u_view_matrix = self.get_program_uniform("u_view_matrix").unwrap();
-        let u_proj_matrix = self.get_program_uniform("u_proj_matrix").unwrap();
-        unsafe {
-            gl::UniformMatrix4fv(
-                u_model_matrix.id,
-                1,
-                gl::FALSE,
-                &model_matrix.columns(0, 4)[0],
-            );
-            gl::UniformMatrix4fv(
-                u_view_matrix.id,
-                1,
-                gl::FALSE,
-                &view_matrix.columns(0, 4)[0],
-            );
-            gl::UniformMatrix4fv(
-                u_proj_matrix.id,
-                1,
-                gl::FALSE,
-                &proj_matrix.columns(0, 4)[0],
-            );
+        self.draw_with_uniforms(mesh, model_matrix, view_matrix, proj_matrix, &[])
+    }
 
-            // Setup geometry for rendering
-            for i in 0..mesh.geometry.len() {
-                mesh.geometry[i].vbo.bind();
-                mesh.geometry[i].ibo.bind();
-                mesh.geometry[i].vao.enable(i as u32);
+    /// Draws a mesh like `draw`, and also sets some extra uniforms first. Uniforms that the current program doesn't
+    /// declare, including the matrices and `u_normal_matrix`, are skipped. Programs that read the view and projection
+    /// matrices from the camera block don't declare them as plain uniforms, so they aren't re-uploaded per draw.
+    pub fn draw_with_uniforms(
+        &self,
+        mesh: &Mesh,
+        model_matrix: nalgebra_glm::Mat4,
+        view_matrix: nalgebra_glm::Mat4,
+        proj_matrix: nalgebra_glm::Mat4,
+        uniforms: &[(&str, UniformValue)],
+    ) {
+        let matrices = [
+            ("u_model_matrix", UniformValue::Mat4(model_matrix)),
+            ("u_view_matrix", UniformValue::Mat4(view_matrix)),
+            ("u_proj_matrix", UniformValue::Mat4(proj_matrix)),
+        ];
+        for (name, value) in matrices.iter().chain(uniforms) {
+            if let Ok(uniform) = self.get_program_uniform(name) {
+                uniform.set(value);
             }
+        }
 
-            // Make the render call!
-            gl::DrawElements(
-                gl::TRIANGLES,
-                mesh.indices.len() as i32,
-                gl::UNSIGNED_INT,
-                0 as *const _,
-            );
-
-            // Unbind all buffers
-            for i in 0..mesh.geometry.len() {
-                mesh.geometry[i].vbo.unbind();
-                mesh.geometry[i].ibo.unbind();
-            }
+        // Normals have to be transformed by the inverse transpose, so that they stay perpendicular to the surface under
+        // non-uniform scale. Inverting is slow, so only do it for programs that use it.
+        if let Ok(u_normal_matrix) = self.get_program_uniform("u_normal_matrix") {
+            let normal_matrix = nalgebra_glm::transpose(&nalgebra_glm::inverse(
+                &nalgebra_glm::mat4_to_mat3(&model_matrix),
+            ));
+            u_normal_matrix.set(&UniformValue::Mat3(normal_matrix));
         }
+
+        mesh.draw_elements(usize::MAX);
     }
 
     pub fn draw_line_path(
@@ -454,6 +929,10 @@ impl<Resource, Id: OpaqueId + Debug> ResourceManager<Resource, Id> {
         self.resources.get(id.as_usize())
     }
 
+    pub fn get_mut_from_id(&mut self, id: Id) -> Option<&mut Resource> {
+        self.resources.get_mut(id.as_usize())
+    }
+
     pub fn get_id_from_name(&self, name: &'static str) -> Option<Id> {
         self.keys.get(name).copied()
     }
@@ -488,6 +967,32 @@ impl OpaqueId for TextureId {
     }
 }
 
+impl OpaqueId for MaterialId {
+    fn new(id: usize) -> Self {
+        MaterialId(id)
+    }
+
+    fn as_usize(&self) -> usize {
+        self.0
+    }
+}
+
+impl Material {
+    /// Creates a material that draws with a program, without any uniforms of its own
+    pub fn new(program: ProgramId) -> Self {
+        Self {
+            program,
+            uniforms: vec![],
+        }
+    }
+
+    /// Adds a uniform that's uploaded whenever a model with the material is drawn
+    pub fn with_uniform(mut self, name: &'static str, value: UniformValue) -> Self {
+        self.uniforms.push((name, value));
+        self
+    }
+}
+
 impl OpaqueId for ProgramId {
     fn new(id: usize) -> Self {
         ProgramId(id)
@@ -499,6 +1004,9 @@ impl OpaqueId for ProgramId {
 }
 
 impl ModelComponent {
+    /// How much larger outlines are than their models by default
+    pub const DEFAULT_OUTLINE_WIDTH: f32 = 0.2;
+
     pub fn new(
         mesh_id: MeshId,
         texture_id: TextureId,
@@ -512,10 +1020,21 @@ impl ModelComponent {
             scale,
             model_matrix: Self::construct_model_matrix(&position, &scale),
             shown: true,
+            layer_mask: DEFAULT_LAYER,
             outlined: false,
+            material: None,
+            outline_color: None,
+            outline_width: Self::DEFAULT_OUTLINE_WIDTH,
+            alpha_cutout: None,
+            extra_textures: vec![],
         }
     }
 
+    /// Whether the model is shown, and on a layer in `mask`
+    pub fn is_visible_in(&self, mask: u32) -> bool {
+        self.shown && self.layer_mask & mask != 0
+    }
+
     pub fn set_position(&mut self, position: nalgebra_glm::Vec3) {
         self.position = position;
         self.regen_model_matrix();
@@ -534,6 +1053,25 @@ impl ModelComponent {
         self.scale
     }
 
+    /// Binds another texture to the model, to the sampler uniform `uniform_name`
+    pub fn add_texture(&mut self, texture_id: TextureId, uniform_name: &'static str) {
+        self.extra_textures.push((texture_id, uniform_name));
+    }
+
+    /// Binds a tangent-space normal map to the model, to the `NORMAL_MAP_UNIFORM` sampler
+    pub fn set_normal_map(&mut self, texture_id: TextureId) {
+        self.extra_textures
+            .retain(|(_, uniform_name)| *uniform_name != NORMAL_MAP_UNIFORM);
+        self.add_texture(texture_id, NORMAL_MAP_UNIFORM);
+    }
+
+    /// Whether a normal map is bound to the model
+    pub fn has_normal_map(&self) -> bool {
+        self.extra_textures
+            .iter()
+            .any(|(_, uniform_name)| *uniform_name == NORMAL_MAP_UNIFORM)
+    }
+
     pub fn get_model_matrix(&self) -> nalgebra_glm::Mat4 {
         self.model_matrix
     }
@@ -605,93 +1143,348 @@ impl LinePathComponent {
 }
 
 impl Mesh {
+    /// Creates a mesh from a list of attributes with three components per vertex. Meshes with exactly positions,
+    /// normals, and texture coordinates also get tangents, for normal mapping.
     pub fn new(indices: Vec<u32>, datas: Vec<&Vec<f32>>) -> Self {
-        let geometry: Vec<GeometryData> = datas
+        let tangents = match datas[..] {
+            [positions, normals, uvs] => Some(compute_tangents(&indices, positions, normals, uvs)),
+            _ => None,
+        };
+        let mut mesh =
+            Self::from_attributes(indices, datas.into_iter().map(|data| (data, 3)).collect());
+        mesh.tangents = tangents.map(|tangents| {
+            let tangents = GeometryData::new(
+                &tangents,
+                4,
+                GeometryDataIndex::Tangent as u32,
+                &mesh.indices,
+            );
+            tangents.vbo.unbind();
+            tangents.ibo.unbind();
+            tangents
+        });
+        mesh
+    }
+
+    /// Creates a mesh without indices, whose vertices are drawn in order as a given primitive, like `gl::POINTS` or
+    /// `gl::LINES`. Each attribute has three components per vertex.
+    pub fn new_unindexed(primitive: gl::types::GLenum, datas: Vec<&Vec<f32>>) -> Self {
+        let mut mesh =
+            Self::from_attributes(vec![], datas.into_iter().map(|data| (data, 3)).collect());
+        mesh.indexed = false;
+        mesh.primitive = primitive;
+        mesh
+    }
+
+    /// Sets the primitive the mesh's vertices are drawn as. Meshes are drawn as `gl::TRIANGLES` by default.
+    pub fn with_primitive(mut self, primitive: gl::types::GLenum) -> Self {
+        self.primitive = primitive;
+        self
+    }
+
+    /// The primitive the mesh's vertices are drawn as
+    pub fn primitive(&self) -> gl::types::GLenum {
+        self.primitive
+    }
+
+    /// Creates a flat-shaded mesh of a height field, `width` cells on a side, starting at `origin`. See
+    /// `MeshData::from_heightmap` for the layout.
+    pub fn from_heightmap(hm: &dyn HeightMap, origin: nalgebra_glm::Vec2, width: usize) -> Self {
+        let data = MeshData::from_heightmap(hm, origin, width);
+        let attributes = data.attributes.iter().map(|(values, _)| values).collect();
+        Self::new(data.indices, attributes)
+    }
+
+    /// Creates a mesh with a per-vertex RGBA color, in the `GeometryDataIndex::Color` slot. Positions, normals, and
+    /// texture coordinates have three components per vertex, colors have four.
+    pub fn new_with_colors(
+        indices: Vec<u32>,
+        positions: &Vec<f32>,
+        normals: &Vec<f32>,
+        uvs: &Vec<f32>,
+        colors: &Vec<f32>,
+    ) -> Self {
+        Self::from_attributes(
+            indices,
+            vec![(positions, 3), (normals, 3), (uvs, 3), (colors, 4)],
+        )
+    }
+
+    /// Creates a mesh with per-vertex colors like `new_with_colors`, and four per-vertex weights, in the
+    /// `GeometryDataIndex::MaterialWeights` slot, that say how much each of up to four materials contributes to a vertex.
+    pub fn new_with_material_weights(
+        indices: Vec<u32>,
+        positions: &Vec<f32>,
+        normals: &Vec<f32>,
+        uvs: &Vec<f32>,
+        colors: &Vec<f32>,
+        weights: &Vec<f32>,
+    ) -> Self {
+        Self::from_attributes(
+            indices,
+            vec![
+                (positions, 3),
+                (normals, 3),
+                (uvs, 3),
+                (colors, 4),
+                (weights, 4),
+            ],
+        )
+    }
+
+    /// Uploads a mesh from CPU-side data. Tangents are uploaded as they were saved, rather than being recomputed.
+    pub fn from_data(data: &MeshData) -> Self {
+        let attributes = data
+            .attributes
             .iter()
-            .map(|data| GeometryData {
-                ibo: Buffer::<u32>::gen(gl::ELEMENT_ARRAY_BUFFER),
-                vao: Vao::gen(),
-                vbo: Buffer::<f32>::gen(gl::ARRAY_BUFFER),
-                vertex_data: data.to_vec(),
-            })
+            .map(|(values, components)| (values, *components))
             .collect();
+        let mut mesh = Self::from_attributes(data.indices.clone(), attributes);
+        mesh.indexed = data.indexed;
+        mesh.primitive = data.primitive;
+        mesh.aabb = data.aabb;
+        mesh.tangents = data.tangents.as_ref().map(|tangents| {
+            let tangents = GeometryData::new(
+                tangents,
+                4,
+                GeometryDataIndex::Tangent as u32,
+                &mesh.indices,
+            );
+            tangents.vbo.unbind();
+            tangents.ibo.unbind();
+            tangents
+        });
+        mesh
+    }
 
-        // Allocate VRAM buffers (this is slow!)
-        for i in 0..geometry.len() {
-            geometry[i].vbo.set_data(&geometry[i].vertex_data);
-            geometry[i].ibo.set_data(&indices);
-            geometry[i].vao.set(i as u32)
+    /// Copies this mesh's geometry back out of its CPU-side buffers, so that it can be saved
+    pub fn to_data(&self) -> MeshData {
+        MeshData {
+            indices: self.indices.clone(),
+            attributes: self
+                .geometry
+                .iter()
+                .map(|data| (data.vertex_data.clone(), data.components))
+                .collect(),
+            tangents: self
+                .tangents
+                .as_ref()
+                .map(|tangents| tangents.vertex_data.clone()),
+            indexed: self.indexed,
+            primitive: self.primitive,
+            aabb: self.aabb,
         }
+    }
 
-        // Unbind all buffers
-        for i in 0..geometry.len() {
-            geometry[i].vbo.unbind();
-            geometry[i].ibo.unbind();
+    /// Draws this mesh's primitives, with only the vertex attributes at locations below `attributes` bound
+    pub(crate) fn draw_elements(&self, attributes: usize) {
+        let tangents = self
+            .tangents
+            .as_ref()
+            .filter(|_| attributes > GeometryDataIndex::Tangent as usize)
+            .map(|tangents| (GeometryDataIndex::Tangent as usize, tangents));
+        let geometry: Vec<(usize, &GeometryData)> = self
+            .geometry
+            .iter()
+            .enumerate()
+            .take(attributes)
+            .chain(tangents)
+            .collect();
+        unsafe {
+            // Setup geometry for rendering
+            for (location, data) in &geometry {
+                data.vbo.bind();
+                if self.indexed {
+                    data.ibo.bind();
+                }
+                data.vao.enable(*location as u32);
+            }
+
+            // Make the render call!
+            if self.indexed {
+                gl::DrawElements(
+                    self.primitive,
+                    self.indices.len() as i32,
+                    gl::UNSIGNED_INT,
+                    0 as *const _,
+                );
+            } else {
+                gl::DrawArrays(self.primitive, 0, self.vertex_count() as i32);
+            }
+
+            // Unbind all buffers
+            for (_, data) in &geometry {
+                data.vbo.unbind();
+                if self.indexed {
+                    data.ibo.unbind();
+                }
+            }
         }
+    }
 
-        let aabb = AABB::from_points(
-            geometry[GeometryDataIndex::Vertex as usize]
-                .vertex_data
-                .chunks(3)
-                .map(|p| nalgebra_glm::vec3(p[0], p[1], p[2])),
-        );
+    /// The number of vertices in this mesh
+    pub fn vertex_count(&self) -> usize {
+        self.positions().len() / 3
+    }
 
-        Mesh {
-            geometry,
-            indices,
-            aabb,
+    /// The number of triangles in this mesh. Meshes of points or lines have no triangles.
+    pub fn triangle_count(&self) -> usize {
+        let elements = if self.indexed {
+            self.indices.len()
+        } else {
+            self.vertex_count()
+        };
+        match self.primitive {
+            gl::TRIANGLES => elements / 3,
+            gl::TRIANGLE_STRIP | gl::TRIANGLE_FAN => elements.saturating_sub(2),
+            _ => 0,
         }
     }
 
-    pub fn from_obj(obj_file_data: &[u8]) -> Self {
-        let obj: Obj<TexturedVertex> = load_obj(&obj_file_data[..]).unwrap();
-        let vb: Vec<TexturedVertex> = obj.vertices;
+    /// Whether this mesh has per-vertex colors
+    pub fn has_colors(&self) -> bool {
+        self.geometry.len() > GeometryDataIndex::Color as usize
+    }
 
-        let indices = vec_u32_from_vec_u16(&obj.indices);
-        let vertices = flatten_positions(&vb);
-        let normals = flatten_normals(&vb);
-        let uv = flatten_uv(&vb);
+    /// Whether this mesh has per-vertex tangents, for normal mapping
+    pub fn has_tangents(&self) -> bool {
+        self.tangents.is_some()
+    }
 
-        let data = vec![&vertices, &normals, &uv];
+    /// Whether this mesh has per-vertex material weights
+    pub fn has_material_weights(&self) -> bool {
+        self.geometry.len() > GeometryDataIndex::MaterialWeights as usize
+    }
 
-        Self::new(indices, data)
+    fn from_attributes(indices: Vec<u32>, datas: Vec<(&Vec<f32>, i32)>) -> Self {
+        let geometry: Vec<GeometryData> = datas
+            .iter()
+            .enumerate()
+            .map(|(i, (data, components))| GeometryData::new(data, *components, i as u32, &indices))
+            .collect();
+
+        // Unbind all buffers
+        for data in &geometry {
+            data.vbo.unbind();
+            data.ibo.unbind();
+        }
+
+        let mut mesh = Mesh {
+            geometry,
+            tangents: None,
+            indices,
+            indexed: true,
+            primitive: gl::TRIANGLES,
+            aabb: AABB::new(),
+        };
+        mesh.recompute_aabb();
+        mesh
     }
-}
 
-fn flatten_positions(vertices: &Vec<TexturedVertex>) -> Vec<f32> {
-    let mut retval = vec![];
-    for vertex in vertices {
-        retval.push(vertex.position[0]);
-        retval.push(vertex.position[1]);
-        retval.push(vertex.position[2]);
+    /// The position of each vertex, as consecutive x, y, z triples
+    pub fn positions(&self) -> &[f32] {
+        &self.geometry[GeometryDataIndex::Vertex as usize].vertex_data
     }
-    retval
-}
 
-fn flatten_normals(vertices: &Vec<TexturedVertex>) -> Vec<f32> {
-    let mut retval = vec![];
-    for vertex in vertices {
-        retval.push(vertex.normal[0]);
-        retval.push(vertex.normal[1]);
-        retval.push(vertex.normal[2]);
+    /// Replaces this mesh's vertex positions, re-uploads them to VRAM, and re-fits the AABB. There must be the same
+    /// number of positions as before. Normals and tangents are left as they were.
+    pub fn set_positions(&mut self, positions: &[f32]) -> Result<(), String> {
+        let data = &mut self.geometry[GeometryDataIndex::Vertex as usize];
+        if positions.len() != data.vertex_data.len() {
+            return Err(format!(
+                "mesh has {} position floats, but {} were given",
+                data.vertex_data.len(),
+                positions.len()
+            ));
+        }
+        data.vertex_data.copy_from_slice(positions);
+        data.vbo.set_data(&data.vertex_data);
+        data.vbo.unbind();
+        self.recompute_aabb();
+        Ok(())
     }
-    retval
-}
 
-fn flatten_uv(vertices: &Vec<TexturedVertex>) -> Vec<f32> {
-    let mut retval = vec![];
-    for vertex in vertices {
-        retval.push(vertex.texture[0]);
-        retval.push(vertex.texture[1]);
-        retval.push(vertex.texture[2]);
+    /// Re-fits this mesh's AABB to its vertex positions. Call this after the positions change, so that culling stays
+    /// correct.
+    pub fn recompute_aabb(&mut self) {
+        self.aabb = AABB::from_points(
+            self.positions()
+                .chunks(3)
+                .map(|p| nalgebra_glm::vec3(p[0], p[1], p[2])),
+        );
+    }
+
+    /// Loads a mesh from the contents of an OBJ file. See `MeshData::from_obj` for how corners become vertices.
+    pub fn from_obj(obj_file_data: &[u8]) -> Result<Self, String> {
+        let data = MeshData::from_obj(obj_file_data)?;
+        let attributes = data.attributes.iter().map(|(values, _)| values).collect();
+        Ok(Self::new(data.indices, attributes))
     }
-    retval
 }
 
-fn vec_u32_from_vec_u16(input: &Vec<u16>) -> Vec<u32> {
-    let mut retval = vec![];
-    for x in input {
-        retval.push(*x as u32);
+/// Computes a tangent for each vertex of a triangle mesh, pointing along increasing U in world space. Each tangent has
+/// a fourth component of 1 or -1, giving the handedness of the bitangent, `cross(normal, tangent) * w`. Vertices whose
+/// texture coordinates are degenerate get an arbitrary tangent perpendicular to their normal.
+fn compute_tangents(indices: &[u32], positions: &[f32], normals: &[f32], uvs: &[f32]) -> Vec<f32> {
+    let vertex_count = positions.len() / 3;
+    let vec3_at =
+        |data: &[f32], i: usize| nalgebra_glm::vec3(data[i * 3], data[i * 3 + 1], data[i * 3 + 2]);
+    let mut tangents = vec![nalgebra_glm::Vec3::zeros(); vertex_count];
+    let mut bitangents = vec![nalgebra_glm::Vec3::zeros(); vertex_count];
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        ];
+        if a.max(b).max(c) >= vertex_count || uvs.len() < vertex_count * 3 {
+            continue;
+        }
+        let edge1 = vec3_at(positions, b) - vec3_at(positions, a);
+        let edge2 = vec3_at(positions, c) - vec3_at(positions, a);
+        let duv1 = vec3_at(uvs, b) - vec3_at(uvs, a);
+        let duv2 = vec3_at(uvs, c) - vec3_at(uvs, a);
+
+        let det = duv1.x * duv2.y - duv2.x * duv1.y;
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) / det;
+        let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / det;
+        for i in [a, b, c] {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    let mut retval = Vec::with_capacity(vertex_count * 4);
+    for i in 0..vertex_count {
+        let normal = if normals.len() >= (i + 1) * 3 {
+            vec3_at(normals, i)
+        } else {
+            WORLD_UP
+        };
+        // Gram-Schmidt orthogonalize against the normal
+        let tangent = (tangents[i] - normal * normal.dot(&tangents[i]))
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(|| any_perpendicular(normal));
+        let handedness = if normal.cross(&tangent).dot(&bitangents[i]) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        retval.extend_from_slice(&[tangent.x, tangent.y, tangent.z, handedness]);
     }
     retval
 }
+
+/// Finds some unit vector perpendicular to a vector
+fn any_perpendicular(v: nalgebra_glm::Vec3) -> nalgebra_glm::Vec3 {
+    let axis = if v.x.abs() < 0.9 {
+        nalgebra_glm::vec3(1.0, 0.0, 0.0)
+    } else {
+        nalgebra_glm::vec3(0.0, 1.0, 0.0)
+    };
+    v.cross(&axis).try_normalize(f32::EPSILON).unwrap_or(axis)
+}
@@ -3,14 +3,68 @@ use std::borrow::Borrow;
 use hecs::{Entity, World};
 
 use super::{
-    aabb::AABB,
     bvh::BVH,
     camera::{Camera, ProjectionKind},
     frustum::Frustum,
-    opengl::{Fbo, Texture},
-    render_core::{ModelComponent, RenderContext},
+    opengl::{Fbo, Texture, UniformValue},
+    render_core::{
+        GeometryDataIndex, Mesh, ModelComponent, OpaqueId, ProgramId, RenderContext, TextureId,
+    },
+    transform::model_matrix_of,
 };
 
+const DEPTH_ONLY_VERT: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 position;
+
+uniform mat4 u_model_matrix;
+uniform mat4 u_view_matrix;
+uniform mat4 u_proj_matrix;
+
+void main() {
+    gl_Position = u_proj_matrix * u_view_matrix * u_model_matrix * vec4(position, 1.0);
+}
+"#;
+
+const DEPTH_ONLY_FRAG: &str = r#"
+#version 330 core
+
+void main() {}
+"#;
+
+/// Like `DEPTH_ONLY_VERT`, but also passes the texture coordinate through, for `DEPTH_CUTOUT_FRAG`
+const DEPTH_CUTOUT_VERT: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 position;
+layout (location = 2) in vec3 uv;
+
+uniform mat4 u_model_matrix;
+uniform mat4 u_view_matrix;
+uniform mat4 u_proj_matrix;
+
+out vec2 v_uv;
+
+void main() {
+    v_uv = uv.xy;
+    gl_Position = u_proj_matrix * u_view_matrix * u_model_matrix * vec4(position, 1.0);
+}
+"#;
+
+/// Discards the same fragments that a model's alpha cutout does, so that foliage casts leaf-shaped shadows
+const DEPTH_CUTOUT_FRAG: &str = r#"
+#version 330 core
+in vec2 v_uv;
+
+uniform sampler2D texture0;
+uniform float u_alpha_cutout;
+
+void main() {
+    if (texture(texture0, v_uv).a < u_alpha_cutout) {
+        discard;
+    }
+}
+"#;
+
 pub struct DirectionalLightSource {
     pub shadow_camera: Camera,
     frame_buffer: Fbo,
@@ -28,9 +82,9 @@ impl DirectionalLightSource {
         let depth_map = Texture::new();
         depth_map.load_depth_buffer(frame_buffer_size, frame_buffer_size);
         let frame_buffer = Fbo::new();
-        frame_buffer.bind();
+        let scope = frame_buffer.bind_scoped();
         depth_map.post_bind();
-        frame_buffer.unbind();
+        drop(scope);
         Self {
             shadow_camera,
             frame_buffer,
@@ -48,13 +102,79 @@ impl DirectionalLightSource {
 }
 
 impl RenderContext {
+    /// Draws only the depth of a mesh into the currently bound framebuffer, from the light's point of view. No textures
+    /// or lighting are involved, and only vertex positions are bound.
+    pub fn draw_depth_only(
+        &self,
+        mesh: &Mesh,
+        model_matrix: nalgebra_glm::Mat4,
+        light_view: nalgebra_glm::Mat4,
+        light_proj: nalgebra_glm::Mat4,
+    ) {
+        let program_id = self.get_or_add_program("depth-only", DEPTH_ONLY_VERT, DEPTH_ONLY_FRAG);
+        self.set_depth_uniforms(program_id, model_matrix, light_view, light_proj, &[]);
+        mesh.draw_elements(GeometryDataIndex::Vertex as usize + 1);
+    }
+
+    /// Draws only the depth of a mesh like `draw_depth_only`, but discards the fragments whose alpha in `texture_id`
+    /// is below `alpha_cutout`, like the 3D pass does. Texture coordinates are bound as well as vertex positions.
+    pub fn draw_depth_cutout(
+        &self,
+        mesh: &Mesh,
+        texture_id: TextureId,
+        alpha_cutout: f32,
+        model_matrix: nalgebra_glm::Mat4,
+        light_view: nalgebra_glm::Mat4,
+        light_proj: nalgebra_glm::Mat4,
+    ) {
+        let program_id =
+            self.get_or_add_program("depth-cutout", DEPTH_CUTOUT_VERT, DEPTH_CUTOUT_FRAG);
+        self.set_depth_uniforms(
+            program_id,
+            model_matrix,
+            light_view,
+            light_proj,
+            &[("u_alpha_cutout", UniformValue::Float(alpha_cutout))],
+        );
+
+        let texture = self.get_texture_from_id(texture_id).unwrap();
+        texture.activate(gl::TEXTURE0);
+        texture.associate_uniform(self.get_current_program_id(), 0, "texture0");
+
+        mesh.draw_elements(GeometryDataIndex::Texture as usize + 1);
+    }
+
+    /// Switches to a depth program, if it isn't already current, and sets its uniforms
+    fn set_depth_uniforms(
+        &self,
+        program_id: ProgramId,
+        model_matrix: nalgebra_glm::Mat4,
+        light_view: nalgebra_glm::Mat4,
+        light_proj: nalgebra_glm::Mat4,
+        extra_uniforms: &[(&str, UniformValue)],
+    ) {
+        let current = self.program.borrow().map(|id| id.as_usize());
+        if current != Some(program_id.as_usize()) {
+            self.set_program_from_id(program_id);
+        }
+
+        let uniforms = [
+            ("u_model_matrix", UniformValue::Mat4(model_matrix)),
+            ("u_view_matrix", UniformValue::Mat4(light_view)),
+            ("u_proj_matrix", UniformValue::Mat4(light_proj)),
+        ];
+        for (name, value) in uniforms.iter().chain(extra_uniforms) {
+            self.get_program_uniform(name).unwrap().set(value);
+        }
+    }
+
     pub fn directional_light_system(
         &self,
         directional_light: &mut DirectionalLightSource,
-        world: &mut World,
+        world: &World,
         bvh: &BVH<Entity>,
     ) {
-        directional_light.frame_buffer.bind();
+        let _scope = directional_light.frame_buffer.bind_scoped();
         unsafe {
             gl::Viewport(
                 0,
@@ -68,9 +188,6 @@ impl RenderContext {
             gl::Clear(gl::DEPTH_BUFFER_BIT)
         }
 
-        // Use a simple depth shader program
-        self.set_program_from_id(self.get_program_id_from_name("shadow").unwrap());
-
         // Compute the camera frustum corners
         let (view_matrix, proj_matrix) = self.camera.borrow().view_proj_matrices();
         let inv_proj_view = nalgebra_glm::inverse(&(proj_matrix * view_matrix));
@@ -92,7 +209,7 @@ impl RenderContext {
         let light_view_frustum = screen_frustum.transform(light_view_matrix);
 
         // Calculate an AABB for the light-view-space frustum
-        let aabb_light_space = AABB::from_points(light_view_frustum.corners());
+        let aabb_light_space = light_view_frustum.bounding_aabb();
 
         // Calculate a light-space AABB for the world
         // let mut world_aabb_light_space = AABB::new();
@@ -116,7 +233,7 @@ impl RenderContext {
         let light_view_frustum = screen_frustum.transform(light_view_matrix);
 
         // Create an Orthographic Projection around the light-space AABB
-        let aabb_light_space = AABB::from_points(light_view_frustum.corners());
+        let aabb_light_space = light_view_frustum.bounding_aabb();
         directional_light.shadow_camera.projection_kind = ProjectionKind::Orthographic {
             left: aabb_light_space.min.x,
             right: aabb_light_space.max.x,
@@ -129,23 +246,32 @@ impl RenderContext {
         let frustum2 =
             Frustum::from_inv_proj_view(directional_light.shadow_camera.inv_proj_view(), false);
 
+        let layer_mask = *self.layer_mask.borrow();
         for model_id in bvh.iter_frustum(&frustum2, false) {
             let model = world.get::<&ModelComponent>(model_id).unwrap();
+            if !model.is_visible_in(layer_mask) {
+                continue;
+            }
             let mesh = self.get_mesh_from_id(model.mesh_id).unwrap();
-            let texture = self.get_texture_from_id(model.texture_id).unwrap();
-            let model_matrix = model.get_model_matrix();
-
-            texture.activate(gl::TEXTURE0);
-            texture.associate_uniform(self.get_current_program_id(), 0, "texture0");
-            self.draw(
-                mesh.borrow(),
-                model_matrix,
-                light_view_matrix,
-                light_proj_matrix,
-            )
+            let model_matrix = model_matrix_of(world, model_id, &model);
+
+            match model.alpha_cutout {
+                Some(alpha_cutout) => self.draw_depth_cutout(
+                    mesh.borrow(),
+                    model.texture_id,
+                    alpha_cutout,
+                    model_matrix,
+                    light_view_matrix,
+                    light_proj_matrix,
+                ),
+                None => self.draw_depth_only(
+                    mesh.borrow(),
+                    model_matrix,
+                    light_view_matrix,
+                    light_proj_matrix,
+                ),
+            }
         }
         // println!("rendered: {}", rendered);
-
-        directional_light.frame_buffer.unbind();
     }
 }
@@ -1,4 +1,4 @@
-use super::frustum::Frustum;
+use super::{aabb::AABB, frustum::Frustum};
 
 #[derive(Debug)]
 pub struct Sphere {
@@ -11,12 +11,141 @@ impl Sphere {
         Self { center, radius }
     }
 
+    /// Create a bounding sphere around an iterator of points, using Ritter's algorithm. The sphere is snug, but not
+    /// guaranteed to be the smallest possible.
+    pub fn from_points(points: impl IntoIterator<Item = nalgebra_glm::Vec3>) -> Self {
+        let points: Vec<nalgebra_glm::Vec3> = points.into_iter().collect();
+        let Some(&first) = points.first() else {
+            return Self::new(nalgebra_glm::zero(), 0.0);
+        };
+
+        // Start with a sphere spanning two points that are roughly the furthest apart
+        let furthest_from = |from: nalgebra_glm::Vec3| {
+            points
+                .iter()
+                .copied()
+                .max_by(|a, b| {
+                    nalgebra_glm::distance2(a, &from).total_cmp(&nalgebra_glm::distance2(b, &from))
+                })
+                .unwrap()
+        };
+        let x = furthest_from(first);
+        let y = furthest_from(x);
+        let mut retval = Self::new((x + y) / 2.0, nalgebra_glm::distance(&x, &y) / 2.0);
+
+        // Grow the sphere just enough to cover any points left outside of it
+        for point in &points {
+            let dist = nalgebra_glm::distance(point, &retval.center);
+            if dist > retval.radius {
+                let new_radius = (retval.radius + dist) / 2.0;
+                retval.center += (point - retval.center) * ((new_radius - retval.radius) / dist);
+                retval.radius = new_radius;
+            }
+        }
+        retval
+    }
+
     pub fn within_frustum(&self, frustum: &Frustum) -> bool {
         for plane in frustum.planes() {
-            if plane.normal().dot(&self.center) + plane.dist() + self.radius < 0.0 {
+            if plane.signed_distance(self.center) < -self.radius {
                 return false;
             }
         }
         true
     }
+
+    /// Determines whether two spheres overlap. Spheres that are just touching count as overlapping.
+    pub fn intersects_sphere(&self, other: &Sphere) -> bool {
+        let radii = self.radius + other.radius;
+        nalgebra_glm::distance2(&self.center, &other.center) <= radii * radii
+    }
+
+    /// Determines whether a sphere overlaps an AABB. Shapes that are just touching count as overlapping.
+    pub fn intersects_aabb(&self, aabb: &AABB) -> bool {
+        let closest = aabb.closest_point(self.center);
+        nalgebra_glm::distance2(&closest, &self.center) <= self.radius * self.radius
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn contains_all(sphere: &Sphere, points: &[nalgebra_glm::Vec3]) -> bool {
+        points
+            .iter()
+            .all(|p| nalgebra_glm::distance(p, &sphere.center) <= sphere.radius + 1e-5)
+    }
+
+    #[test]
+    fn from_points_around_a_cube() {
+        let corners = AABB::from_min_max(
+            nalgebra_glm::vec3(-1.0, -1.0, -1.0),
+            nalgebra_glm::vec3(1.0, 1.0, 1.0),
+        )
+        .corners();
+        let sphere = Sphere::from_points(corners);
+        assert!(nalgebra_glm::distance(&sphere.center, &nalgebra_glm::zero()) < 1e-5);
+        assert!((sphere.radius - 3.0_f32.sqrt()).abs() < 1e-5);
+        assert!(contains_all(&sphere, &corners));
+    }
+
+    #[test]
+    fn from_points_contains_every_point() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let points: Vec<nalgebra_glm::Vec3> = (0..200)
+            .map(|_| {
+                nalgebra_glm::vec3(
+                    rng.gen_range(-5.0..5.0),
+                    rng.gen_range(-2.0..8.0),
+                    rng.gen_range(0.0..3.0),
+                )
+            })
+            .collect();
+        let sphere = Sphere::from_points(points.iter().copied());
+        assert!(contains_all(&sphere, &points));
+        // Never worse than the sphere around the points' AABB
+        let aabb_radius = AABB::from_points(points.iter().copied())
+            .bounding_sphere()
+            .radius;
+        assert!(sphere.radius <= aabb_radius);
+    }
+
+    #[test]
+    fn from_no_points_is_empty() {
+        assert_eq!(Sphere::from_points([]).radius, 0.0);
+    }
+
+    #[test]
+    fn spheres_touching_overlapping_and_separated() {
+        let sphere = Sphere::new(nalgebra_glm::vec3(0.0, 0.0, 0.0), 1.0);
+        let touching = Sphere::new(nalgebra_glm::vec3(3.0, 0.0, 0.0), 2.0);
+        let overlapping = Sphere::new(nalgebra_glm::vec3(0.0, 1.5, 0.0), 1.0);
+        let separated = Sphere::new(nalgebra_glm::vec3(0.0, 0.0, -3.0), 1.5);
+        assert!(sphere.intersects_sphere(&touching));
+        assert!(sphere.intersects_sphere(&overlapping));
+        assert!(!sphere.intersects_sphere(&separated));
+    }
+
+    #[test]
+    fn sphere_touching_overlapping_and_separated_from_an_aabb() {
+        let aabb = AABB::from_min_max(
+            nalgebra_glm::vec3(0.0, 0.0, 0.0),
+            nalgebra_glm::vec3(2.0, 2.0, 2.0),
+        );
+        let touching_face = Sphere::new(nalgebra_glm::vec3(1.0, 1.0, 5.0), 3.0);
+        // (3, 4, 0) is exactly 5 units long, so this just touches the edge at x = 2, y = 2
+        let touching_edge = Sphere::new(nalgebra_glm::vec3(5.0, 6.0, 1.0), 5.0);
+        let overlapping = Sphere::new(nalgebra_glm::vec3(-0.5, 1.0, 1.0), 1.0);
+        let inside = Sphere::new(nalgebra_glm::vec3(1.0, 1.0, 1.0), 0.5);
+        let separated = Sphere::new(nalgebra_glm::vec3(-2.0, -2.0, -2.0), 3.0);
+        assert!(touching_face.intersects_aabb(&aabb));
+        assert!(touching_edge.intersects_aabb(&aabb));
+        assert!(overlapping.intersects_aabb(&aabb));
+        assert!(inside.intersects_aabb(&aabb));
+        assert!(!separated.intersects_aabb(&aabb));
+    }
 }
@@ -0,0 +1,133 @@
+//! This module implements a pool of worker threads, for CPU-heavy work like generating terrain or decoding images.
+//!
+//! OpenGL can only be used from the main thread, so work is split in two. The task itself runs on a worker and must
+//! not touch the renderer. Its result is sent back to the main thread, where it can be uploaded to the GPU, either by
+//! polling a `TaskHandle` or by a callback passed to `App::spawn_task_then`.
+
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A fixed number of worker threads that run tasks from a shared queue
+pub struct TaskPool {
+    // Dropped before the workers are joined, which tells them to stop
+    sender: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+/// The pending result of a task
+pub struct TaskHandle<T> {
+    receiver: Receiver<T>,
+    state: TaskState,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum TaskState {
+    Running,
+    Finished,
+    Failed,
+}
+
+impl TaskPool {
+    /// Starts a pool with `threads` workers. At least one worker is always started.
+    pub fn new(threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads.max(1))
+            .map(|i| {
+                let receiver = Arc::clone(&receiver);
+                std::thread::Builder::new()
+                    .name(format!("apricot-worker-{}", i))
+                    .spawn(move || loop {
+                        // Only hold the lock while waiting, so other workers can take jobs while this one runs
+                        let job = receiver.lock().unwrap().recv();
+                        match job {
+                            // A panicking task fails its handle, but shouldn't take the worker down with it
+                            Ok(job) => {
+                                let _ = catch_unwind(AssertUnwindSafe(job));
+                            }
+                            Err(_) => break,
+                        }
+                    })
+                    .unwrap()
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    /// The number of workers to start by default, which leaves one core for the main thread
+    pub fn default_threads() -> usize {
+        std::thread::available_parallelism()
+            .map(|n| n.get().saturating_sub(1))
+            .unwrap_or(1)
+            .max(1)
+    }
+
+    /// Queues a task to run on a worker. The task must not use OpenGL.
+    pub fn spawn<T: Send + 'static>(
+        &self,
+        task: impl FnOnce() -> T + Send + 'static,
+    ) -> TaskHandle<T> {
+        let (result_sender, receiver) = mpsc::channel();
+        let job: Job = Box::new(move || {
+            // The handle may have been dropped, in which case nobody wants the result
+            let _ = result_sender.send(task());
+        });
+        self.sender.as_ref().unwrap().send(job).unwrap();
+        TaskHandle {
+            receiver,
+            state: TaskState::Running,
+        }
+    }
+}
+
+impl Drop for TaskPool {
+    fn drop(&mut self) {
+        // Workers finish the tasks already queued, then stop once the queue is closed
+        self.sender = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl<T> TaskHandle<T> {
+    /// Takes the task's result if it has finished. Returns `None` while the task is running, and after the result has
+    /// been taken.
+    pub fn poll(&mut self) -> Option<T> {
+        if self.state != TaskState::Running {
+            return None;
+        }
+        match self.receiver.try_recv() {
+            Ok(result) => {
+                self.state = TaskState::Finished;
+                Some(result)
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.state = TaskState::Failed;
+                None
+            }
+        }
+    }
+
+    /// Whether the task is still running, as of the last call to `poll`
+    pub fn is_running(&self) -> bool {
+        self.state == TaskState::Running
+    }
+
+    /// Whether the task panicked before producing a result, as of the last call to `poll`
+    pub fn is_failed(&self) -> bool {
+        self.state == TaskState::Failed
+    }
+}
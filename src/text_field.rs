@@ -0,0 +1,149 @@
+//! This module implements a single-line text field, for things like consoles and naming dialogs.
+//!
+//! Text fields are split into `update`, which handles input once per tick, and `draw`, which renders the field. Text is
+//! measured and drawn with the renderer's current font, and only printable ASCII characters can be typed.
+
+use sdl2::keyboard::Scancode;
+
+use super::{app::App, rectangle::Rectangle};
+
+/// An editable line of text, with a caret that can be moved with the arrow keys
+pub struct TextField {
+    /// The area the field is drawn in. Text outside of it is clipped.
+    pub rect: Rectangle,
+    /// Whether the field receives keyboard input. Clicking on the field focuses it, and clicking elsewhere unfocuses
+    /// it.
+    pub focused: bool,
+    text: String,
+    // Index of the character the caret is before. Only ASCII can be typed, so this is also a byte index.
+    cursor: usize,
+    // How far the text is scrolled to the left, so that the caret stays inside of the field
+    scroll: f32,
+    // When the text or caret last changed, so that the caret stays solid while typing
+    last_edit_seconds: f32,
+}
+
+impl TextField {
+    const PADDING: f32 = 4.0;
+    const CARET_WIDTH: f32 = 1.0;
+    const BLINK_SECONDS: f32 = 0.5;
+
+    const BACKGROUND_COLOR: nalgebra_glm::Vec4 = nalgebra_glm::Vec4::new(0.1, 0.1, 0.1, 0.8);
+    const FOCUSED_COLOR: nalgebra_glm::Vec4 = nalgebra_glm::Vec4::new(0.15, 0.15, 0.2, 0.9);
+    const TEXT_COLOR: nalgebra_glm::Vec4 = nalgebra_glm::Vec4::new(1.0, 1.0, 1.0, 1.0);
+
+    /// Creates an empty, unfocused text field
+    pub fn new(rect: Rectangle) -> Self {
+        Self {
+            rect,
+            focused: false,
+            text: String::new(),
+            cursor: 0,
+            scroll: 0.0,
+            last_edit_seconds: 0.0,
+        }
+    }
+
+    /// The current text in the field
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the text in the field, and moves the caret to the end
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.chars().filter(Self::is_typeable).collect();
+        self.cursor = self.text.len();
+    }
+
+    /// Handles this tick's input. Returns the current text, and whether Enter was pressed.
+    pub fn update(&mut self, app: &App) -> (&str, bool) {
+        if app.mouse_left_clicked {
+            self.focused = self.rect.contains_point(&app.mouse_pos);
+        }
+        if !self.focused {
+            return (&self.text, false);
+        }
+
+        let mut entered = false;
+        for &sc in &app.keys_pressed {
+            match sc {
+                Scancode::Return | Scancode::KpEnter => entered = true,
+                Scancode::Backspace if self.cursor > 0 => {
+                    self.cursor -= 1;
+                    self.text.remove(self.cursor);
+                }
+                Scancode::Delete if self.cursor < self.text.len() => {
+                    self.text.remove(self.cursor);
+                }
+                Scancode::Left => self.cursor = self.cursor.saturating_sub(1),
+                Scancode::Right => self.cursor = (self.cursor + 1).min(self.text.len()),
+                Scancode::Home => self.cursor = 0,
+                Scancode::End => self.cursor = self.text.len(),
+                _ => continue,
+            }
+            self.last_edit_seconds = app.seconds;
+        }
+
+        // Editing keys are handled before the typed text, since SDL reports them separately
+        for c in app.text_input.chars().filter(Self::is_typeable) {
+            self.text.insert(self.cursor, c);
+            self.cursor += 1;
+            self.last_edit_seconds = app.seconds;
+        }
+
+        if app.renderer.font.borrow().is_some() {
+            self.scroll_to_caret(app);
+        }
+        (&self.text, entered)
+    }
+
+    /// Draws the field, its text, and its caret if it's focused
+    pub fn draw(&self, app: &App) {
+        let renderer = &app.renderer;
+        let old_color = *renderer.color.borrow();
+        renderer.set_color(if self.focused {
+            Self::FOCUSED_COLOR
+        } else {
+            Self::BACKGROUND_COLOR
+        });
+        renderer.fill_rect(self.rect);
+
+        if renderer.font.borrow().is_some() {
+            renderer.push_scissor(self.rect);
+            renderer.set_color(Self::TEXT_COLOR);
+            let text_pos = self.rect.pos + nalgebra_glm::vec2(Self::PADDING - self.scroll, 0.0);
+            renderer.draw_text(text_pos, &self.text);
+
+            // Blink, but stay solid for a moment after each edit
+            let since_edit = app.seconds - self.last_edit_seconds;
+            let blink_on = since_edit % (Self::BLINK_SECONDS * 2.0) < Self::BLINK_SECONDS;
+            if self.focused && blink_on {
+                let before_caret = renderer.measure_text(&self.text[..self.cursor]);
+                let line_height = renderer.measure_text("").y;
+                renderer.fill_rect(Rectangle::new(
+                    text_pos.x + before_caret.x,
+                    text_pos.y,
+                    Self::CARET_WIDTH,
+                    line_height,
+                ));
+            }
+            renderer.pop_scissor();
+        }
+        renderer.set_color(old_color);
+    }
+
+    /// Scrolls the text so that the caret is inside of the field
+    fn scroll_to_caret(&mut self, app: &App) {
+        let caret_x = app.renderer.measure_text(&self.text[..self.cursor]).x;
+        let visible_width = (self.rect.size.x - Self::PADDING * 2.0 - Self::CARET_WIDTH).max(0.0);
+        if caret_x < self.scroll {
+            self.scroll = caret_x;
+        } else if caret_x > self.scroll + visible_width {
+            self.scroll = caret_x - visible_width;
+        }
+    }
+
+    fn is_typeable(c: &char) -> bool {
+        c.is_ascii_graphic() || *c == ' '
+    }
+}
@@ -0,0 +1,206 @@
+//! This module implements parent/child transform hierarchies, so that objects can be attached to other objects and
+//! follow them around.
+
+use std::{collections::HashMap, fmt::Debug, hash::Hash};
+
+use hecs::{Entity, World};
+
+use super::{
+    aabb::AABB,
+    bvh::{BVHNodeId, BVH},
+    render_core::{ModelComponent, RenderContext},
+};
+
+/// A position, rotation, and scale relative to an optional parent entity. Models with a `Transform` are drawn with its
+/// world matrix instead of their own model matrix, and their position and scale are kept in sync with it.
+pub struct Transform {
+    pub position: nalgebra_glm::Vec3,
+    pub rotation: nalgebra_glm::Quat,
+    pub scale: nalgebra_glm::Vec3,
+    /// The entity this transform is relative to. Parents without a `Transform` are treated as the origin.
+    pub parent: Option<Entity>,
+    /// The BVH node of the entity's model, which is moved along with the transform
+    pub bvh_node: Option<BVHNodeId>,
+    world_matrix: nalgebra_glm::Mat4,
+}
+
+impl Transform {
+    /// Create a new transform with no rotation and no parent
+    pub fn new(position: nalgebra_glm::Vec3, scale: nalgebra_glm::Vec3) -> Self {
+        Self {
+            position,
+            rotation: nalgebra_glm::quat_identity(),
+            scale,
+            parent: None,
+            bvh_node: None,
+            world_matrix: nalgebra_glm::one(),
+        }
+    }
+
+    /// Attaches the transform to a parent entity
+    pub fn with_parent(mut self, parent: Entity) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Moves the given BVH node along with the transform
+    pub fn with_bvh_node(mut self, bvh_node: BVHNodeId) -> Self {
+        self.bvh_node = Some(bvh_node);
+        self
+    }
+
+    /// The matrix of the transform relative to its parent
+    pub fn local_matrix(&self) -> nalgebra_glm::Mat4 {
+        let translated = nalgebra_glm::translate(&nalgebra_glm::one(), &self.position);
+        let rotated = translated * nalgebra_glm::quat_to_mat4(&self.rotation);
+        nalgebra_glm::scale(&rotated, &self.scale)
+    }
+
+    /// The matrix of the transform in world space, as of the last call to `propagate_transforms`
+    pub fn world_matrix(&self) -> nalgebra_glm::Mat4 {
+        self.world_matrix
+    }
+}
+
+/// Computes the world matrix of every transform by walking up its parent chain. Returns an error if the parents form a
+/// cycle.
+///
+/// Models with a transform have their position and scale set from its world matrix, so that physics and
+/// `RenderContext::get_model_aabb` see where they're drawn, and their BVH node is moved to cover them.
+pub fn propagate_transforms(
+    world: &mut World,
+    renderer: &RenderContext,
+    bvh: &mut BVH<Entity>,
+) -> Result<(), String> {
+    let locals: HashMap<Entity, (nalgebra_glm::Mat4, Option<Entity>)> = world
+        .query::<&Transform>()
+        .iter()
+        .map(|(entity, transform)| (entity, (transform.local_matrix(), transform.parent)))
+        .collect();
+    let world_matrices = compute_world_matrices(&locals)?;
+
+    for (entity, (transform, model)) in
+        world.query_mut::<(&mut Transform, Option<&mut ModelComponent>)>()
+    {
+        let world_matrix = world_matrices[&entity];
+        transform.world_matrix = world_matrix;
+        let Some(model) = model else {
+            continue;
+        };
+
+        let old_position = model.get_position();
+        let position = world_matrix.column(3).xyz();
+        model.set_position(position);
+        model.set_scale(nalgebra_glm::vec3(
+            world_matrix.column(0).xyz().magnitude(),
+            world_matrix.column(1).xyz().magnitude(),
+            world_matrix.column(2).xyz().magnitude(),
+        ));
+
+        if let Some(bvh_node) = transform.bvh_node {
+            // Transform every corner, so that the AABB still covers the model when it's rotated
+            let mesh_aabb = renderer.get_mesh_aabb(model.mesh_id);
+            let aabb = AABB::from_points(
+                mesh_aabb
+                    .corners()
+                    .iter()
+                    .map(|corner| (world_matrix * corner.push(1.0)).xyz()),
+            );
+            bvh.move_obj(bvh_node, &aabb, &(position - old_position));
+        }
+    }
+    Ok(())
+}
+
+/// Finds the world matrix of every node in a hierarchy, from each node's local matrix and parent. Parents that aren't
+/// in the hierarchy are treated as the origin.
+fn compute_world_matrices<K: Copy + Eq + Hash + Debug>(
+    locals: &HashMap<K, (nalgebra_glm::Mat4, Option<K>)>,
+) -> Result<HashMap<K, nalgebra_glm::Mat4>, String> {
+    let mut world_matrices: HashMap<K, nalgebra_glm::Mat4> = HashMap::new();
+    for &node in locals.keys() {
+        // Walk up until reaching a root, or an ancestor whose world matrix is already known
+        let mut chain = vec![];
+        let mut base: nalgebra_glm::Mat4 = nalgebra_glm::one();
+        let mut current = Some(node);
+        while let Some(ancestor) = current {
+            if let Some(matrix) = world_matrices.get(&ancestor) {
+                base = *matrix;
+                break;
+            }
+            let Some(&(_, parent)) = locals.get(&ancestor) else {
+                break;
+            };
+            if chain.contains(&ancestor) {
+                return Err(format!("transform hierarchy has a cycle at {:?}", ancestor));
+            }
+            chain.push(ancestor);
+            current = parent;
+        }
+
+        for ancestor in chain.iter().rev() {
+            base *= locals[ancestor].0;
+            world_matrices.insert(*ancestor, base);
+        }
+    }
+    Ok(world_matrices)
+}
+
+/// The matrix a model should be drawn with, which is the world matrix of its `Transform` if it has one
+pub(crate) fn model_matrix_of(
+    world: &World,
+    entity: Entity,
+    model: &ModelComponent,
+) -> nalgebra_glm::Mat4 {
+    match world.get::<&Transform>(entity) {
+        Ok(transform) => transform.world_matrix(),
+        Err(_) => model.get_model_matrix(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation(x: f32, y: f32, z: f32) -> nalgebra_glm::Mat4 {
+        nalgebra_glm::translate(&nalgebra_glm::one(), &nalgebra_glm::vec3(x, y, z))
+    }
+
+    #[test]
+    fn children_follow_their_parents() {
+        let locals = HashMap::from([
+            (0, (translation(1.0, 0.0, 0.0), None)),
+            (1, (translation(0.0, 2.0, 0.0), Some(0))),
+            (2, (translation(0.0, 0.0, 3.0), Some(1))),
+        ]);
+        let world_matrices = compute_world_matrices(&locals).unwrap();
+        assert_eq!(
+            world_matrices[&2].column(3).xyz(),
+            nalgebra_glm::vec3(1.0, 2.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn missing_parents_are_the_origin() {
+        let locals = HashMap::from([(0, (translation(1.0, 2.0, 3.0), Some(7)))]);
+        let world_matrices = compute_world_matrices(&locals).unwrap();
+        assert_eq!(world_matrices[&0], translation(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let locals = HashMap::from([
+            (0, (translation(1.0, 0.0, 0.0), Some(2))),
+            (1, (translation(1.0, 0.0, 0.0), Some(0))),
+            (2, (translation(1.0, 0.0, 0.0), Some(1))),
+            (3, (translation(1.0, 0.0, 0.0), None)),
+        ]);
+        assert!(compute_world_matrices(&locals).is_err());
+    }
+
+    #[test]
+    fn self_parents_are_rejected() {
+        let locals = HashMap::from([(0, (translation(1.0, 0.0, 0.0), Some(0)))]);
+        assert!(compute_world_matrices(&locals).is_err());
+    }
+}